@@ -0,0 +1,275 @@
+//  animation.rs -- objects which move every frame.
+//
+//  Part of render-bench.
+//
+//  Vehicles driving along the streets, and signs turning on their posts.
+//  Each gets a new transform every frame through set_object_transform,
+//  either from the render thread between frames or from a worker thread
+//  of its own. The content creation threads keep adding and dropping
+//  buildings meanwhile, so the cost per object is measured under the same
+//  contention for the renderer as everything else, and reported for each
+//  thing the builder was doing at the time.
+//
+use super::random::Rng;
+use super::solids::{self, SceneObject, TextureSet};
+use super::terrain::Terrain;
+use glam::{Mat4, Quat, Vec2, Vec3};
+use rend3::Renderer;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// One in this many animated objects is a sign. The rest are vehicles.
+const SIGN_EVERY: usize = 4;
+/// Vehicle speeds, meters per second.
+const SPEED_RANGE: (f32, f32) = (4.0, 12.0);
+/// Sign turning rate, radians per second.
+const SPIN_RANGE: (f32, f32) = (0.5, 2.0);
+
+/// Which thread updates the animated objects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationThread {
+    Render, // on the render thread, before each frame
+    Worker, // on a thread of its own, at about 60 updates per second
+}
+
+impl AnimationThread {
+    /// Parse from command line
+    pub fn parse(s: &str) -> Result<AnimationThread, &'static str> {
+        match s {
+            "render" => Ok(AnimationThread::Render),
+            "worker" => Ok(AnimationThread::Worker),
+            _ => Err("Animation thread must be render or worker"),
+        }
+    }
+
+    /// Name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimationThread::Render => "render",
+            AnimationThread::Worker => "worker",
+        }
+    }
+}
+
+/// A straight two-way road vehicles can drive along.
+#[derive(Debug, Copy, Clone)]
+pub struct Road {
+    pub start: Vec3, // center line, one end
+    pub end: Vec3,   // center line, other end
+    pub lane: f32,   // lane centers are this far either side of the center line
+    pub curb: f32,   // signs go this far either side of the center line
+}
+
+/// How an animated object moves.
+#[derive(Debug, Copy, Clone)]
+enum Motion {
+    Drive {
+        start: Vec3,     // lane start
+        direction: Vec3, // unit vector along lane
+        length: f32,     // lane length. Vehicles wrap around to the start.
+        speed: f32,      // meters per second
+        offset: f32,     // distance along lane at time 0
+    },
+    Spin {
+        pos: Vec3,  // base of post
+        rate: f32,  // radians per second
+        angle: f32, // angle at time 0
+    },
+}
+
+impl Motion {
+    /// Transform at this time.
+    fn transform(&self, terrain: &Terrain, t: f32) -> Mat4 {
+        match *self {
+            Motion::Drive {
+                start,
+                direction,
+                length,
+                speed,
+                offset,
+            } => {
+                let mut pos = start + direction * (offset + speed * t).rem_euclid(length);
+                pos.y += terrain.height_at(Vec2::new(pos.x, pos.z));
+                let yaw = (-direction.z).atan2(direction.x); // +X turned to direction
+                Mat4::from_rotation_translation(Quat::from_rotation_y(yaw), pos)
+            }
+            Motion::Spin { pos, rate, angle } => {
+                Mat4::from_rotation_translation(Quat::from_rotation_y(angle + rate * t), pos)
+            }
+        }
+    }
+}
+
+/// Update statistics while the builder was in one phase.
+#[derive(Debug, Default, Clone)]
+struct Tally {
+    frames: usize,   // updates of all objects
+    objects: usize,  // object updates
+    total: Duration, // time spent updating
+    worst: Duration, // longest update of all objects
+}
+
+/// Objects which get a new transform every frame.
+pub struct Animator {
+    movers: Vec<(SceneObject, Motion)>,  // objects, and how each moves
+    terrain: Terrain,                    // vehicles follow the ground
+    thread: AnimationThread,             // who calls update, for reports
+    start: Instant,                      // time 0 for motions
+    tallies: Vec<(&'static str, Tally)>, // by builder phase, since last report
+    last_report: Instant,                // when last reported
+}
+
+impl Animator {
+    /// Make count objects, spread over the roads.
+    pub fn new(
+        renderer: &Arc<Renderer>,
+        count: usize,
+        roads: &[Road],
+        terrain: Terrain,
+        (body, sign): (&TextureSet, &TextureSet),
+        thread: AnimationThread,
+        seed: u64,
+    ) -> Animator {
+        profiling::scope!("Add animated objects");
+        let mut rng = Rng::new(seed);
+        let mut movers = Vec::with_capacity(count);
+        if !roads.is_empty() {
+            for n in 0..count {
+                let road = roads[rng.below(roads.len())];
+                let along = road.end - road.start;
+                let length = along.length();
+                let direction = along / length;
+                let side = direction.cross(Vec3::Y); // to the right of direction
+                let motion = if n % SIGN_EVERY == SIGN_EVERY - 1 {
+                    let curb = if rng.chance(0.5) {
+                        road.curb
+                    } else {
+                        -road.curb
+                    };
+                    let mut pos = road.start + direction * rng.range(0.0, length) + side * curb;
+                    pos.y += terrain.height_at(Vec2::new(pos.x, pos.z));
+                    Motion::Spin {
+                        pos,
+                        rate: rng.range(SPIN_RANGE.0, SPIN_RANGE.1),
+                        angle: rng.range(0.0, 2.0 * core::f32::consts::PI),
+                    }
+                } else if rng.chance(0.5) {
+                    Motion::Drive {
+                        start: road.start + side * road.lane,
+                        direction,
+                        length,
+                        speed: rng.range(SPEED_RANGE.0, SPEED_RANGE.1),
+                        offset: rng.range(0.0, length),
+                    }
+                } else {
+                    //  Other lane, other way.
+                    Motion::Drive {
+                        start: road.end - side * road.lane,
+                        direction: -direction,
+                        length,
+                        speed: rng.range(SPEED_RANGE.0, SPEED_RANGE.1),
+                        offset: rng.range(0.0, length),
+                    }
+                };
+                let object = match motion {
+                    Motion::Drive { .. } => vehicle(renderer, body),
+                    Motion::Spin { .. } => spinning_sign(renderer, sign),
+                };
+                movers.push((object, motion));
+            }
+        }
+        let mut animator = Animator {
+            movers,
+            terrain,
+            thread,
+            start: Instant::now(),
+            tallies: Vec::new(),
+            last_report: Instant::now(),
+        };
+        animator.update(renderer, "starting"); // into place before the first frame
+        animator.tallies.clear();
+        animator
+    }
+
+    /// Move every object to where it is now. Phase is what the builder is doing, for reports.
+    pub fn update(&mut self, renderer: &Arc<Renderer>, phase: &'static str) {
+        if self.movers.is_empty() {
+            return;
+        }
+        profiling::scope!("Animate");
+        let t = self.start.elapsed().as_secs_f32();
+        let start = Instant::now();
+        for (object, motion) in &mut self.movers {
+            object.set_transform(renderer, motion.transform(&self.terrain, t));
+        }
+        let elapsed = start.elapsed();
+        let tally = match self.tallies.iter().position(|(name, _)| *name == phase) {
+            Some(n) => &mut self.tallies[n].1,
+            None => {
+                self.tallies.push((phase, Tally::default()));
+                &mut self.tallies.last_mut().unwrap().1
+            }
+        };
+        tally.frames += 1;
+        tally.objects += self.movers.len();
+        tally.total += elapsed;
+        tally.worst = tally.worst.max(elapsed);
+    }
+
+    /// Every 10 seconds, print the cost per object for each builder phase. Then clear.
+    pub fn report(&mut self) {
+        if self.last_report.elapsed() < Duration::from_secs(10) || self.tallies.is_empty() {
+            return;
+        }
+        println!(
+            "Animation: {} objects moved each frame, from the {} thread.",
+            self.movers.len(),
+            self.thread.name()
+        );
+        for (phase, tally) in &self.tallies {
+            println!(
+                "  while {:<18} {:>7} frames. Per object: {:0>5.2}us. Per frame: {:.2}ms average, {:.2}ms worst",
+                phase,
+                tally.frames,
+                tally.total.as_secs_f32() * 1_000_000.0 / tally.objects.max(1) as f32,
+                tally.total.as_secs_f32() * 1000.0 / tally.frames.max(1) as f32,
+                tally.worst.as_secs_f32() * 1000.0
+            );
+        }
+        self.tallies.clear();
+        self.last_report = Instant::now();
+    }
+}
+
+/// A car. Body and cabin, merged, facing +X, wheels implied.
+fn vehicle(renderer: &Arc<Renderer>, texture_set: &TextureSet) -> SceneObject {
+    const CLEARANCE: f32 = 0.3;
+    let parts = [
+        (
+            Vec3::new(4.0, 0.9, 1.8),
+            Vec3::new(0.0, CLEARANCE + 0.45, 0.0),
+        ), // body
+        (
+            Vec3::new(2.2, 0.7, 1.6),
+            Vec3::new(-0.3, CLEARANCE + 0.9 + 0.35, 0.0),
+        ), // cabin
+        (
+            Vec3::new(3.4, CLEARANCE, 1.6),
+            Vec3::new(0.0, CLEARANCE * 0.5, 0.0),
+        ), // wheels and underside
+    ];
+    solids::create_merged_block(renderer, &parts, Vec3::ZERO, Quat::IDENTITY, texture_set)
+}
+
+/// A sign on a post, which turns about the post.
+fn spinning_sign(renderer: &Arc<Renderer>, texture_set: &TextureSet) -> SceneObject {
+    const HEIGHT: f32 = 3.0;
+    let parts = [
+        (
+            Vec3::new(0.1, HEIGHT, 0.1),
+            Vec3::new(0.0, HEIGHT * 0.5, 0.0),
+        ), // post
+        (Vec3::new(1.2, 0.6, 0.05), Vec3::new(0.0, HEIGHT + 0.3, 0.0)), // board, on top
+    ];
+    solids::create_merged_block(renderer, &parts, Vec3::ZERO, Quat::IDENTITY, texture_set)
+}
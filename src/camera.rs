@@ -0,0 +1,104 @@
+//  camera.rs -- camera projection and starting pose.
+//
+//  Part of render-bench.
+//
+//  The usual camera is a perspective one, at eye level near the middle of
+//  the city. The top-down camera is orthographic, straight down from above,
+//  sized to take in the whole city. That puts every object in view at once,
+//  the worst case for culling and draw submission.
+//
+use core::f32::consts::{FRAC_PI_2, FRAC_PI_4, FRAC_PI_8};
+use glam::{Vec3, Vec3A};
+use rend3::types::CameraProjection;
+
+/// Height of the top-down camera above the ground, meters.
+//  Above the tallest building. Rend3's orthographic view box is centered on
+//  the camera, so it reaches this far below the camera, and a bit more.
+const TOP_DOWN_HEIGHT: f32 = 200.0;
+
+/// Kind of projection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Projection {
+    Perspective, // from the camera position, as set
+    TopDown,     // orthographic, looking straight down on the whole city
+}
+
+impl Projection {
+    /// Parse from command line
+    pub fn parse(s: &str) -> Result<Projection, &'static str> {
+        match s {
+            "perspective" => Ok(Projection::Perspective),
+            "top-down" => Ok(Projection::TopDown),
+            _ => Err("Projection must be perspective or top-down"),
+        }
+    }
+}
+
+/// Camera settings from the command line.
+#[derive(Debug, Copy, Clone)]
+pub struct CameraSettings {
+    pub projection: Projection, // perspective or top-down
+    pub vfov: f32,              // vertical field of view, degrees, for perspective
+    pub near: f32,              // near plane distance, meters, for perspective
+    pub position: Vec3,         // starting position
+    pub yaw: f32,               // starting heading, radians, 0 toward -Z
+    pub pitch: f32,             // starting pitch, radians, up positive
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            projection: Projection::Perspective,
+            vfov: 60.0,
+            near: 0.1,
+            position: Vec3::new(3.0, 2.0, 3.0),
+            yaw: FRAC_PI_4,
+            pitch: -FRAC_PI_8,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Starting position, yaw and pitch. For top-down, above the center, looking down.
+    pub fn start_pose(&self) -> (Vec3A, f32, f32) {
+        match self.projection {
+            Projection::Perspective => (self.position.into(), self.yaw, self.pitch),
+            Projection::TopDown => (Vec3A::new(0.0, TOP_DOWN_HEIGHT, 0.0), 0.0, -FRAC_PI_2),
+        }
+    }
+
+    /// Rend3 projection. Extent is the width of the city, aspect the window's width over height.
+    pub fn camera_projection(&self, extent: f32, aspect: f32) -> CameraProjection {
+        match self.projection {
+            Projection::Perspective => CameraProjection::Perspective {
+                vfov: self.vfov,
+                near: self.near,
+            },
+            Projection::TopDown => {
+                //  Whole city in view, whichever way the window is longer.
+                let height = if aspect >= 1.0 {
+                    extent
+                } else {
+                    extent / aspect
+                };
+                CameraProjection::Orthographic {
+                    size: Vec3A::new(height * aspect, height, TOP_DOWN_HEIGHT * 2.5),
+                }
+            }
+        }
+    }
+}
+
+/// Parse starting orientation from command line. Yaw and pitch, in degrees.
+pub fn parse_camera_angles(value: &str) -> Result<(f32, f32), &'static str> {
+    let split: Vec<_> = value.split(',').map(|v| v.trim()).collect();
+    if split.len() != 2 {
+        return Err("Camera angles are defined with 2 values, yaw and pitch");
+    }
+    let yaw: f32 = split[0].parse().map_err(|_| "Cannot parse camera yaw")?;
+    let pitch: f32 = split[1].parse().map_err(|_| "Cannot parse camera pitch")?;
+    if !(-90.0..=90.0).contains(&pitch) {
+        return Err("Camera pitch must be between -90 and 90 degrees");
+    }
+    Ok((yaw.to_radians(), pitch.to_radians()))
+}
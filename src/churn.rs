@@ -0,0 +1,163 @@
+//  churn.rs -- change live objects.
+//
+//  Part of render-bench.
+//
+//  Adding and dropping whole buildings is not the only way scenes change.
+//  Doors open, textures change, prims get edited. This picks a fraction of
+//  the live objects each second and changes each one in one of three ways,
+//  through the Rend3 API, and times each kind of change:
+//
+//      transform   set_object_transform, swinging the object a little
+//      material    update_material, tinting the material in place
+//      mesh        a resized mesh. Rend3 objects cannot change meshes,
+//                  so this is a new mesh and object with the same material.
+//
+//  For comparison, some objects are also made again from scratch, with
+//  new mesh, material and object, so each kind of change can be reported
+//  as a fraction of the cost of creating the object.
+//
+use super::random::Rng;
+use super::solids::SceneObject;
+use glam::{Mat4, Vec3, Vec4};
+use rend3::Renderer;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Kinds of change, and recreation for comparison.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Change {
+    Transform,
+    Material,
+    Mesh,
+    Recreate,
+}
+
+impl Change {
+    const ALL: [Change; 4] = [
+        Change::Transform,
+        Change::Material,
+        Change::Mesh,
+        Change::Recreate,
+    ];
+
+    /// Name for reports
+    fn name(&self) -> &'static str {
+        match self {
+            Change::Transform => "transform",
+            Change::Material => "material",
+            Change::Mesh => "mesh",
+            Change::Recreate => "recreate",
+        }
+    }
+}
+
+/// Changes live objects at a steady rate.
+pub struct Churn {
+    rate: f32,            // fraction of live objects changed per second
+    rng: Rng,             // which objects, and how
+    due: f32,             // changes owed but not yet made
+    counts: [usize; 4],   // changes since last report, indexed by Change
+    times: [Duration; 4], // time spent on them, indexed by Change
+}
+
+impl Churn {
+    /// Usual new
+    pub fn new(rate: f32, seed: u64) -> Churn {
+        Churn {
+            rate,
+            rng: Rng::new(seed),
+            due: 0.0,
+            counts: [0; 4],
+            times: [Duration::ZERO; 4],
+        }
+    }
+
+    /// Number of changes due, with this many live objects, after this much time.
+    pub fn due(&mut self, live: usize, elapsed: Duration) -> usize {
+        self.due += self.rate * live as f32 * elapsed.as_secs_f32();
+        let count = self.due.floor();
+        self.due -= count;
+        count as usize
+    }
+
+    /// Change count objects, chosen at random. Returns number changed.
+    pub fn change(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        objects: &mut [&mut SceneObject],
+        count: usize,
+    ) -> usize {
+        if objects.is_empty() {
+            return 0;
+        }
+        profiling::scope!("Churn");
+        let mut changed = 0;
+        for _ in 0..count {
+            let object = &mut objects[self.rng.below(objects.len())];
+            let change = Change::ALL[self.rng.below(Change::ALL.len())];
+            let start = Instant::now();
+            let done = match change {
+                Change::Transform => {
+                    let angle = self.rng.range(-0.1, 0.1); // radians
+                    object.swing(renderer, Mat4::from_rotation_y(angle));
+                    true
+                }
+                Change::Material => {
+                    let shade = |rng: &mut Rng| rng.range(0.6, 1.0);
+                    let tint = Vec4::new(
+                        shade(&mut self.rng),
+                        shade(&mut self.rng),
+                        shade(&mut self.rng),
+                        1.0,
+                    );
+                    object.tint(renderer, tint);
+                    true
+                }
+                Change::Mesh => object.resize(renderer, Vec3::splat(self.rng.range(0.9, 1.1))),
+                Change::Recreate => object.recreate(renderer),
+            };
+            if done {
+                self.counts[change as usize] += 1;
+                self.times[change as usize] += start.elapsed();
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Print the cost of each kind of change, and compared with recreating. Then clear.
+    pub fn report(&mut self) {
+        let average = |change: Change| {
+            let count = self.counts[change as usize];
+            if count == 0 {
+                None
+            } else {
+                Some(self.times[change as usize].as_secs_f32() / count as f32)
+            }
+        };
+        if self.counts.iter().all(|&count| count == 0) {
+            return; // nothing changed
+        }
+        let recreate = average(Change::Recreate);
+        println!("Object changes:");
+        for change in Change::ALL {
+            let Some(secs) = average(change) else {
+                continue; // none of this kind
+            };
+            print!(
+                "  {:<10} {:>7} changes. Average: {:0>5.2}us",
+                change.name(),
+                self.counts[change as usize],
+                secs * 1_000_000.0
+            );
+            match recreate {
+                Some(recreate) if change != Change::Recreate && recreate > 0.0 => {
+                    println!("; {:.2} of the cost of recreating", secs / recreate)
+                }
+                _ => println!(),
+            }
+        }
+        self.counts = [0; 4];
+        self.times = [Duration::ZERO; 4];
+    }
+}
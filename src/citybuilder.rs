@@ -4,25 +4,53 @@
 //
 //  Used for generating simple 3D scenes for benchmarking purposes.
 //
-use super::solids;
+use super::animation::{AnimationThread, Animator, Road};
+use super::churn::Churn;
+use super::furniture::Furnisher;
+use super::interest::{CameraView, CellKey, UploadOrder, UploadQueue};
+use super::lights::{self, Light, LightBudget, LightKind};
+use super::random::Rng;
+use super::scatter::{Ground, Scatterer};
+use super::solids::{self, AssetCache, Prim, PrimShape, SceneObject, Sharing, TextureSet, Variety};
+use super::stats::{self, Op};
+use super::terrain::{self, Terrain, TerrainTile};
 use core::f32::consts::PI;
-use glam::{Quat, Vec3};
+use glam::{Quat, Vec2, Vec3};
 use image::RgbaImage;
-use rend3::{
-    types::{ObjectHandle, Texture2DHandle},
-    Renderer,
-};
+use rend3::Renderer;
+use rend3_routine::pbr::Transparency;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 //  Supplied parameters for building the city
 #[derive(Debug, Clone)]
 pub struct CityParams {
     texture_dir: String,                               // directory path to content
     texture_files: Vec<(String, String, String, f32)>, // texture name, albedo file, normal file, scale
+    pub bldg_rows: usize,                              // buildings are in a square grid this many on a side
+    pub builder_threads: usize,                        // content creation threads
+    pub sharing: Sharing,                              // what blocks share
+    pub lod_distances: Option<(f32, f32)>,             // full detail inside first, shell inside second, else box
+    pub stream_radius: Option<f32>,                    // if set, load and unload cells around the camera
+    pub upload_order: UploadOrder,                     // order of uploads when streaming
+    pub streets: bool,                                 // streets and sidewalks between buildings
+    pub tessellation: u32,                             // sides of round shapes, 0 for none
+    pub furniture_density: f32,                        // fraction of floor slots furnished, 0 for empty
+    pub scatter_density: f32,                          // trees and street furniture, 1.0 is one per 4m
+    pub terrain_height: f32,                           // height of hills, 0 for flat ground
+    pub terrain_edits: f32,                            // terrain tiles re-uploaded per second
+    pub modify_rate: f32,                              // fraction of live objects changed per second
+    pub animated: usize,                               // vehicles and signs moved every frame
+    pub animation_thread: AnimationThread,             // which thread moves them
+    pub max_lights: usize,                             // window and street lamp lights at once, 0 for none
+    pub glass: Option<Transparency>,                   // glass in windows and doors, and how it is drawn
+    pub material_variants: usize,                      // distinct surfaces per texture set, 1 for all plain
+    pub material_variety: f32,                         // how far variant surfaces stray from plain, 0 to 1
+    pub aomr: bool,                                    // ambient occlusion, metallic and roughness maps too
+    pub seed: u64,                                     // for everything random
 }
 
 impl CityParams {
@@ -41,12 +69,48 @@ impl CityParams {
                     )
                 })
                 .collect(),
+            bldg_rows: 25,
+            builder_threads: 1,
+            sharing: Sharing::None,
+            lod_distances: None,
+            stream_radius: None,
+            upload_order: UploadOrder::Interest,
+            streets: false,
+            tessellation: 0,
+            furniture_density: 0.0,
+            scatter_density: 0.0,
+            terrain_height: 0.0,
+            terrain_edits: 0.0,
+            modify_rate: 0.0,
+            animated: 0,
+            animation_thread: AnimationThread::Worker,
+            max_lights: 0,
+            glass: None,
+            material_variants: 1,
+            material_variety: 0.5,
+            aomr: false,
+            seed: 1,
         }
     }
+
+    /// Distance between building grid cells. Wider if there are streets.
+    pub fn cell_spacing(&self) -> f32 {
+        if self.streets {
+            BLDG_SPACING + STREET_WIDTH
+        } else {
+            BLDG_SPACING
+        }
+    }
+
+    /// Is terrain edited, by a thread of its own?
+    fn edits_terrain(&self) -> bool {
+        self.terrain_height > 0.0 && self.terrain_edits > 0.0
+    }
 }
 
 pub struct CityState {
     pub textures: TextureSetRgbaMap, // map of all the textures, as ImageRgba, not TextureHandle
+    pub lights: Arc<LightBudget>,    // lights all builder threads may add
 }
 
 impl CityState {
@@ -54,18 +118,109 @@ impl CityState {
     pub fn new() -> CityState {
         CityState {
             textures: HashMap::new(),
+            lights: Arc::new(LightBudget::new(0)),
         }
     }
 }
 
+/// What the content creation thread is doing right now.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildPhase {
+    Starting,
+    LoadingTextures,
+    AddingPermanent,
+    Adding,
+    Waiting,
+    Deleting,
+    LodSwap,
+    TerrainEdit,
+    Modifying,
+    Stopped,
+}
+
+impl BuildPhase {
+    const ALL: [BuildPhase; 10] = [
+        BuildPhase::Starting,
+        BuildPhase::LoadingTextures,
+        BuildPhase::AddingPermanent,
+        BuildPhase::Adding,
+        BuildPhase::Waiting,
+        BuildPhase::Deleting,
+        BuildPhase::LodSwap,
+        BuildPhase::TerrainEdit,
+        BuildPhase::Modifying,
+        BuildPhase::Stopped,
+    ];
+
+    /// Name for logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildPhase::Starting => "starting",
+            BuildPhase::LoadingTextures => "loading_textures",
+            BuildPhase::AddingPermanent => "adding_permanent",
+            BuildPhase::Adding => "adding",
+            BuildPhase::Waiting => "waiting",
+            BuildPhase::Deleting => "deleting",
+            BuildPhase::LodSwap => "lod_swap",
+            BuildPhase::TerrainEdit => "terrain_edit",
+            BuildPhase::Modifying => "modifying",
+            BuildPhase::Stopped => "stopped",
+        }
+    }
+
+    /// Set phase, visible to other threads.
+    fn set(self, phase: &AtomicU8) {
+        phase.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// Get phase set by another thread.
+    fn get(phase: &AtomicU8) -> BuildPhase {
+        Self::ALL[phase.load(Ordering::Relaxed) as usize]
+    }
+
+    /// Is the scene being changed in this phase?
+    fn is_mutating(self) -> bool {
+        matches!(
+            self,
+            BuildPhase::AddingPermanent
+                | BuildPhase::Adding
+                | BuildPhase::Deleting
+                | BuildPhase::LodSwap
+                | BuildPhase::TerrainEdit
+                | BuildPhase::Modifying
+        )
+    }
+
+    /// Phase of the builder as a whole, from the phases of all its threads.
+    //  Mutating if any thread is mutating, so frames are labeled by what the
+    //  renderer is contending with. Otherwise the phase of the thread furthest
+    //  behind, so the builder is not waiting until all its threads are.
+    fn combined(phases: &[AtomicU8]) -> BuildPhase {
+        let phases: Vec<BuildPhase> = phases.iter().map(BuildPhase::get).collect();
+        phases
+            .iter()
+            .find(|phase| phase.is_mutating())
+            .or_else(|| phases.iter().min_by_key(|phase| **phase as u8))
+            .copied()
+            .unwrap_or(BuildPhase::Starting)
+    }
+}
+
+/// Terrain tiles, made by one thread and edited by another.
+pub type SharedTerrain = Arc<Mutex<Vec<TerrainTile>>>;
+
 /// City Builder - a very simple procedural content generator.
 //  Just enough to create something complicated to mimic the load of
 //  rendering a few city blocks.
 pub struct CityBuilder {
-    pub threads: Vec<thread::JoinHandle<()>>, // the threads
-    pub state: Arc<Mutex<CityState>>,         // shared state
-    pub stop_flag: Arc<AtomicBool>,           // set to stop
-    pub params: CityParams,                   // params
+    pub threads: Vec<thread::JoinHandle<()>>,   // the threads
+    pub state: Arc<Mutex<CityState>>,           // shared state
+    pub stop_flag: Arc<AtomicBool>,             // set to stop
+    pub phases: Arc<Vec<AtomicU8>>,             // BuildPhase of each builder thread, then terrain
+    pub camera: Arc<Mutex<CameraView>>,         // camera position and direction, from viewer
+    pub params: CityParams,                     // params
+    pub animator: Arc<Mutex<Option<Animator>>>, // moving objects, if the render thread moves them
+    pub terrain: SharedTerrain,                 // terrain tiles, if the ground is not flat
 }
 
 impl CityBuilder {
@@ -75,25 +230,77 @@ impl CityBuilder {
             state: Arc::new(Mutex::new(CityState::new())),
             threads: Vec::new(),
             stop_flag: Arc::new(AtomicBool::new(false)),
+            phases: Arc::new(
+                (0..city_params.builder_threads.max(1) + city_params.edits_terrain() as usize)
+                    .map(|_| AtomicU8::new(BuildPhase::Starting as u8))
+                    .collect(),
+            ),
+            camera: Arc::new(Mutex::new(CameraView::default())),
             params: city_params,
+            animator: Arc::new(Mutex::new(None)),
+            terrain: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
     /// Start and fire off threads.        
-    pub fn start(&mut self, thread_count: usize, renderer: Arc<Renderer>) {
-        assert!(thread_count == 1); // Only one building thread for now.
-        self.init(&renderer); // any needed pre-thread init
+    //  Each thread builds its share of the building rows.
+    pub fn start(&mut self, renderer: Arc<Renderer>) {
+        let thread_count = self.params.builder_threads.max(1);
+        let city_textures = self.init(&renderer); // any needed pre-thread init
         for n in 0..thread_count {
             profiling::scope!("Content creator");
             profiling::register_thread!();
             let renderer_clone = Arc::clone(&renderer);
-            let state_clone = Arc::clone(&self.state);
+            let textures_clone = Arc::clone(&city_textures);
+            let params_clone = self.params.clone();
             let stop_clone = Arc::clone(&self.stop_flag);
+            let phases_clone = Arc::clone(&self.phases);
+            let camera_clone = Arc::clone(&self.camera);
+            let animator_clone = Arc::clone(&self.animator);
+            let terrain_clone = Arc::clone(&self.terrain);
             let handle = thread::spawn(move || {
-                Self::run(state_clone, renderer_clone, n, stop_clone);
+                Self::run(
+                    textures_clone,
+                    renderer_clone,
+                    params_clone,
+                    (n, thread_count),
+                    (stop_clone, phases_clone),
+                    (camera_clone, animator_clone),
+                    terrain_clone,
+                );
             });
             self.threads.push(handle); // accumulate threads
         }
+        //  Animated objects are made by the first builder thread, and moved by this one.
+        if self.params.animated > 0 && self.params.animation_thread == AnimationThread::Worker {
+            let renderer_clone = Arc::clone(&renderer);
+            let stop_clone = Arc::clone(&self.stop_flag);
+            let phases_clone = Arc::clone(&self.phases);
+            let animator_clone = Arc::clone(&self.animator);
+            let handle = thread::spawn(move || {
+                Self::run_animation(renderer_clone, stop_clone, phases_clone, animator_clone);
+            });
+            self.threads.push(handle);
+        }
+        //  Terrain is made by the first builder thread, and edited by this one, on its own schedule.
+        if self.params.edits_terrain() {
+            let renderer_clone = Arc::clone(&renderer);
+            let textures_clone = Arc::clone(&city_textures);
+            let params_clone = self.params.clone();
+            let stop_clone = Arc::clone(&self.stop_flag);
+            let phases_clone = Arc::clone(&self.phases);
+            let terrain_clone = Arc::clone(&self.terrain);
+            let handle = thread::spawn(move || {
+                Self::run_terrain_edits(
+                    renderer_clone,
+                    textures_clone,
+                    params_clone,
+                    (stop_clone, phases_clone, thread_count), // slot after the builder threads
+                    terrain_clone,
+                );
+            });
+            self.threads.push(handle);
+        }
     }
 
     /// Call to shut down
@@ -106,37 +313,111 @@ impl CityBuilder {
         println!("All worker threads shut down.");
     }
 
+    /// What the content creation threads are doing now.
+    pub fn phase(&self) -> BuildPhase {
+        BuildPhase::combined(&self.phases)
+    }
+
+    /// Move the animated objects, if the render thread moves them. Call before each frame.
+    pub fn animate(&self, renderer: &Arc<Renderer>) {
+        if self.params.animation_thread != AnimationThread::Render {
+            return; // worker thread moves them
+        }
+        if let Some(animator) = self.animator.lock().unwrap().as_mut() {
+            animator.update(renderer, self.phase().name());
+            animator.report();
+        }
+    }
+
+    /// Tell the content creation threads where the camera is, and which way it is looking.
+    pub fn set_camera_view(&self, position: Vec3, forward: Vec3) {
+        *self.camera.lock().unwrap() = CameraView { position, forward };
+    }
+
     /// Pre-spawn initialization
-    fn init(&mut self, _renderer: &Renderer) {
+    //  Textures are loaded and uploaded once, here, and shared by all the threads.
+    fn init(&mut self, renderer: &Arc<Renderer>) -> Arc<CityTextures> {
         println!("Loading texture files.");
+        for phase in self.phases.iter() {
+            BuildPhase::LoadingTextures.set(phase);
+        }
         //  Load all the textures
-        self.state.lock().unwrap().textures =
-            TextureSetRgba::new_map(&self.params.texture_dir, &self.params.texture_files);
+        let mut state = self.state.lock().unwrap();
+        state.textures = TextureSetRgba::new_map(
+            &self.params.texture_dir,
+            &self.params.texture_files,
+            self.params.aomr,
+        );
+        state.lights = Arc::new(LightBudget::new(self.params.max_lights));
+        //  Convert all the textures from RGBA to texture handles.
+        let params = &self.params;
+        let city_textures = CityTextures::new_from_map(
+            renderer,
+            &state,
+            params.sharing,
+            BuildingStyle {
+                tessellation: params.tessellation,
+                furniture_density: params.furniture_density,
+                scatter_density: params.scatter_density,
+                terrain: Terrain {
+                    seed: params.seed,
+                    height: params.terrain_height,
+                },
+                glass: params.glass,
+                variety: Variety {
+                    variants: params.material_variants,
+                    amount: params.material_variety,
+                    seed: params.seed,
+                },
+                seed: params.seed,
+            },
+        );
+        stats::op_stats().report("Load textures");
         println!("Content loaded.");
+        Arc::new(city_textures)
     }
 
     /// Actually does the work
+    //  With more than one thread, each thread does every Nth row of buildings,
+    //  and only thread 0 makes the ground. Each thread reports its own call times.
     fn run(
-        state: Arc<Mutex<CityState>>,
+        city_textures: Arc<CityTextures>,
         renderer: Arc<Renderer>,
-        _id: usize,
-        stop_flag: Arc<AtomicBool>,
+        params: CityParams,
+        (id, thread_count): (usize, usize), // which thread, of how many
+        (stop_flag, phases): (Arc<AtomicBool>, Arc<Vec<AtomicU8>>),
+        (camera, animator): (Arc<Mutex<CameraView>>, Arc<Mutex<Option<Animator>>>),
+        terrain_tiles: SharedTerrain,
     ) {
         profiling::register_thread!();
         profiling::scope!("Add content");
-        //  Convert all the textures from RGBA to texture handles.
-        let city_textures = CityTextures::new_from_map(&renderer, &state.lock().unwrap().textures);
+        let phase = &phases[id]; // this thread's slot
+        let report = |phase_name: &str| {
+            if thread_count > 1 {
+                stats::op_stats().report(&format!("{}, builder thread {}", phase_name, id));
+            } else {
+                stats::op_stats().report(phase_name);
+            }
+        };
 
-        //  Make ground plane
+        //  Make ground plane. With streets, each cell has its own ground instead.
         const WORLD_SIZE: f32 = 256.0; // one SL region size
-        let _ground_handle = solids::create_simple_block(
-            &renderer,
-            Vec3::new(WORLD_SIZE, 0.5, WORLD_SIZE), // Ground object
-            Vec3::ZERO,
-            Vec3::new(0.0, -0.25, 0.0), // ground surface is at Z=0.0
-            Quat::IDENTITY,             // no rotation
-            &city_textures.ground,
-        );
+        let terrain = city_textures.style.terrain;
+        let make_ground = id == 0 && params.stream_radius.is_none() && !params.streets;
+        let _ground_handle = if make_ground && terrain.is_flat() {
+            Some(solids::create_simple_block(
+                &renderer,
+                Vec3::new(WORLD_SIZE, 0.5, WORLD_SIZE), // Ground object
+                Vec3::ZERO,
+                Vec3::new(0.0, -0.25, 0.0), // ground surface is at Z=0.0
+                Quat::IDENTITY,             // no rotation
+                &city_textures.ground,
+            ))
+        } else {
+            None
+        };
+        //  Changes to live objects, made while idle, or between cell loads when streaming.
+        let mut churn = Churn::new(params.modify_rate, params.seed.wrapping_add(id as u64));
         //  Building specification.
         //  When we get more ambitious, each building will be different.
         let ground_floor = (
@@ -167,19 +448,242 @@ impl CityBuilder {
             upper_stories,
             upper_stories,
         ];
-        const BLDG_ROWS: usize = 25;
+        //  Or terrain, in tiles which can be edited while running, except where buildings stand.
+        if make_ground && !terrain.is_flat() {
+            let extent = building_extent(&multi_story_building);
+            let footprints: Vec<(Vec2, Vec2)> = (0..params.bldg_rows)
+                .flat_map(|i| (0..params.bldg_rows).map(move |j| (i, j)))
+                .map(|(i, j)| {
+                    let corner = grid_corner(i, j, params.bldg_rows, params.cell_spacing());
+                    let low = Vec2::new(corner.x, corner.z);
+                    (low, low + Vec2::new(extent.x, extent.z))
+                })
+                .collect();
+            *terrain_tiles.lock().unwrap() = terrain::draw_terrain(
+                &renderer,
+                &terrain,
+                WORLD_SIZE,
+                &city_textures.ground,
+                &footprints,
+            );
+        }
+        //  Vehicles and signs, moved every frame by another thread.
+        if id == 0 && params.animated > 0 {
+            let roads = traffic_roads(&params, building_extent(&multi_story_building));
+            *animator.lock().unwrap() = Some(Animator::new(
+                &renderer,
+                params.animated,
+                &roads,
+                terrain,
+                (&city_textures.marble, &city_textures.stone),
+                params.animation_thread,
+                params.seed,
+            ));
+            report("Add animated objects");
+        }
+        let bldg_rows = params.bldg_rows;
+        let my_rows = |rows: core::ops::Range<usize>| {
+            rows.filter(|row| row % thread_count == id).collect::<Vec<usize>>()
+        };
+        //  Level of detail for a building at this position, given where the camera is now.
+        let choose_lod = |center: Vec3| {
+            Lod::for_distance(
+                params.lod_distances,
+                center.distance(camera.lock().unwrap().position),
+            )
+        };
+        //  Wait for 10 seconds, swapping levels of detail as the camera moves.
+        let mut idle = |building_sets: &mut [&mut Vec<Building>]| {
+            profiling::scope!("Idle");
+            BuildPhase::Waiting.set(phase);
+            let mut swapped = 0; // buildings with LOD changed
+            let mut changed = 0; // objects modified
+            let mut last_tick = Instant::now(); // last object changes
+            for _ in 0..100 {
+                if stop_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+                //  Changes to live objects.
+                let live = building_sets
+                    .iter()
+                    .map(|buildings| object_count(buildings))
+                    .sum();
+                let changes_due = churn.due(live, last_tick.elapsed());
+                last_tick = Instant::now();
+                if changes_due > 0 {
+                    profiling::scope!("Modify objects");
+                    BuildPhase::Modifying.set(phase);
+                    let mut objects =
+                        scene_objects(building_sets.iter_mut().flat_map(|set| set.iter_mut()));
+                    changed += churn.change(&renderer, &mut objects, changes_due);
+                    BuildPhase::Waiting.set(phase);
+                }
+                let lod_changed = |buildings: &Vec<Building>| {
+                    buildings.iter().any(|b| choose_lod(b.center) != b.lod)
+                };
+                if params.lod_distances.is_some()
+                    && building_sets.iter().any(|buildings| lod_changed(buildings))
+                {
+                    profiling::scope!("LOD swap");
+                    BuildPhase::LodSwap.set(phase);
+                    swapped += building_sets
+                        .iter_mut()
+                        .map(|buildings| {
+                            update_lods(
+                                &renderer,
+                                buildings,
+                                &choose_lod,
+                                &multi_story_building,
+                                &city_textures,
+                            )
+                        })
+                        .sum::<usize>();
+                    BuildPhase::Waiting.set(phase);
+                }
+            }
+            if swapped > 0 {
+                report(&format!("LOD swaps of {} buildings", swapped));
+            }
+            if changed > 0 {
+                churn.report();
+                report(&format!("Changes to {} objects", changed));
+            }
+        };
+        //  Streaming mode. The city is an unbounded grid of cells, and cells
+        //  are loaded and unloaded as the camera moves, instead of on a timer.
+        if let Some(radius) = params.stream_radius {
+            let spacing = params.cell_spacing();
+            let mut cells: HashMap<CellKey, Cell> = HashMap::new();
+            let mut queue = UploadQueue::new(params.upload_order);
+            let mut last_report = Instant::now();
+            let mut last_tick = Instant::now(); // last object changes
+            //  Cells in range, for this thread, not yet loaded.
+            let wanted = |view: &CameraView, cells: &HashMap<CellKey, Cell>| {
+                cells_in_radius(view.position, radius, spacing)
+                    .into_iter()
+                    .filter(|key| key.0.rem_euclid(thread_count as i32) == id as i32)
+                    .filter(|key| !cells.contains_key(key))
+                    .collect::<Vec<CellKey>>()
+            };
+            //  How much of the screen a cell would cover.
+            let extent = building_extent(&multi_story_building);
+            let interest = |view: CameraView| {
+                move |key: CellKey| {
+                    let center = cell_center(key, spacing) + Vec3::new(0.0, extent.y * 0.5, 0.0);
+                    view.screen_size(center, extent.length() * 0.5)
+                }
+            };
+            while !stop_flag.load(Ordering::Relaxed) {
+                let view = *camera.lock().unwrap();
+                //  Drop cells which are out of range. A bit of hysteresis, to avoid thrashing at the edge.
+                let out_of_range: Vec<CellKey> = cells
+                    .keys()
+                    .filter(|key| {
+                        cell_distance(**key, spacing, view.position) > radius * STREAM_HYSTERESIS
+                    })
+                    .copied()
+                    .collect();
+                if !out_of_range.is_empty() {
+                    profiling::scope!("Delete cells");
+                    BuildPhase::Deleting.set(phase);
+                    for key in out_of_range {
+                        cells.remove(&key).unwrap().delete();
+                    }
+                }
+                //  Queue cells which came into range, and upload them, most interesting first.
+                queue.update(&wanted(&view, &cells), interest(view));
+                if !queue.is_empty() {
+                    profiling::scope!("Add cells");
+                    BuildPhase::Adding.set(phase);
+                    let mut prioritized_for = view;
+                    while let Some(key) = queue.pop() {
+                        if stop_flag.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let cell = draw_cell(
+                            &renderer,
+                            key,
+                            (spacing, params.streets),
+                            &multi_story_building,
+                            &city_textures,
+                            &choose_lod,
+                        );
+                        cells.insert(key, cell);
+                        //  If the camera moved or turned during the upload, reprioritize.
+                        let view = *camera.lock().unwrap();
+                        if view.position.distance(prioritized_for.position) > REPRIORITIZE_DISTANCE
+                            || view.forward.dot(prioritized_for.forward) < REPRIORITIZE_COS_ANGLE
+                        {
+                            queue.update(&wanted(&view, &cells), interest(view));
+                            prioritized_for = view;
+                        }
+                    }
+                }
+                //  Levels of detail for cells already loaded.
+                if params.lod_distances.is_some()
+                    && cells.values().any(|cell| choose_lod(cell.building.center) != cell.building.lod)
+                {
+                    profiling::scope!("LOD swap");
+                    BuildPhase::LodSwap.set(phase);
+                    for cell in cells.values_mut() {
+                        update_lods(
+                            &renderer,
+                            std::slice::from_mut(&mut cell.building),
+                            &choose_lod,
+                            &multi_story_building,
+                            &city_textures,
+                        );
+                    }
+                }
+                //  Changes to live objects.
+                let live = cells
+                    .values()
+                    .map(|cell| cell.building.object_count())
+                    .sum();
+                let changes_due = churn.due(live, last_tick.elapsed());
+                last_tick = Instant::now();
+                if changes_due > 0 {
+                    profiling::scope!("Modify objects");
+                    BuildPhase::Modifying.set(phase);
+                    let mut objects =
+                        scene_objects(cells.values_mut().map(|cell| &mut cell.building));
+                    churn.change(&renderer, &mut objects, changes_due);
+                }
+                BuildPhase::Waiting.set(phase);
+                if last_report.elapsed() >= Duration::from_secs(10) {
+                    println!("Streaming: {} cells loaded.", cells.len());
+                    queue.report();
+                    churn.report();
+                    report("Streaming");
+                    last_report = Instant::now();
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            BuildPhase::Stopped.set(phase);
+            return;
+        }
         //  Draw first building rows once. Draw others and keep redrawing them.
         println!("Adding permanent buildings.");
-        let permanent_buildings = draw_building_grid(
+        BuildPhase::AddingPermanent.set(phase);
+        let _streets = if params.streets {
+            draw_street_grid(&renderer, &my_rows(0..bldg_rows), bldg_rows, &city_textures)
+        } else {
+            Vec::new()
+        };
+        let mut permanent_buildings = draw_building_grid(
             &renderer,
-            0..BLDG_ROWS / 2,
+            &my_rows(0..bldg_rows / 2),
+            (bldg_rows, params.cell_spacing(), params.streets),
             &multi_story_building,
             &city_textures,
+            &choose_lod,
         );
         println!(
             "Adding permanent buildings completed. {} meshes added.",
-            permanent_buildings.len()
+            object_count(&permanent_buildings)
         );
+        report("Add permanent buildings");
         loop {
             if stop_flag.load(Ordering::Relaxed) {
                 break;
@@ -188,46 +692,302 @@ impl CityBuilder {
             let mut temporary_buildings = {
                 profiling::scope!("Add buildings");
                 println!("Adding buildings.");
+                BuildPhase::Adding.set(phase);
                 let result = draw_building_grid(
                     &renderer,
-                    BLDG_ROWS / 2..BLDG_ROWS,
+                    &my_rows(bldg_rows / 2..bldg_rows),
+                    (bldg_rows, params.cell_spacing(), params.streets),
                     &multi_story_building,
                     &city_textures,
+                    &choose_lod,
+                );
+                println!(
+                    "Adding buildings completed. {} meshes added.",
+                    object_count(&result)
                 );
-                println!("Adding buildings completed. {} meshes added.", result.len());
+                report("Add buildings");
                 result
             };
-            {
-                profiling::scope!("Idle");
-                //  Wait for 10 seconds.
-                for _n in 0..100 {
-                    if stop_flag.load(Ordering::Relaxed) {
-                        break;
-                    }
-                    std::thread::sleep(Duration::from_millis(100));
-                }
-            }
+            idle(&mut [&mut permanent_buildings, &mut temporary_buildings]);
             {
                 profiling::scope!("Delete buildings");
                 println!("Deleting buildings.");
-                let cnt = temporary_buildings.len();
-                temporary_buildings.clear(); // drop bulidings
+                BuildPhase::Deleting.set(phase);
+                let cnt = object_count(&temporary_buildings);
+                for building in temporary_buildings.drain(..) {
+                    building.delete(); // drop buildings, one object at a time
+                }
                 println!("Deleting buildings completed. {} meshes deleted.", cnt);
+                report("Delete buildings");
             }
-            {
-                profiling::scope!("Idle");
-                //  Wait for 10 seconds.
-                for _n in 0..100 {
-                    if stop_flag.load(Ordering::Relaxed) {
-                        break;
-                    }
-                    std::thread::sleep(Duration::from_millis(100));
+            idle(&mut [&mut permanent_buildings]);
+        }
+        BuildPhase::Stopped.set(phase);
+    }
+
+    /// Move the animated objects at about the frame rate, on a thread of their own.
+    //  Waits for the first builder thread to make them.
+    fn run_animation(
+        renderer: Arc<Renderer>,
+        stop_flag: Arc<AtomicBool>,
+        phases: Arc<Vec<AtomicU8>>,
+        animator: Arc<Mutex<Option<Animator>>>,
+    ) {
+        profiling::register_thread!();
+        const FRAME_INTERVAL: Duration = Duration::from_micros(16_667); // 60 per second
+        let mut next_frame = Instant::now();
+        while !stop_flag.load(Ordering::Relaxed) {
+            if let Some(animator) = animator.lock().unwrap().as_mut() {
+                animator.update(&renderer, BuildPhase::combined(&phases).name());
+                animator.report();
+            }
+            next_frame += FRAME_INTERVAL;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(wait) => std::thread::sleep(wait),
+                None => next_frame = Instant::now(), // fell behind, do not try to catch up
+            }
+        }
+    }
+
+    /// Edit terrain tiles at the given rate, on a thread of their own, whatever the builders are doing.
+    //  Waits for the first builder thread to make the tiles.
+    fn run_terrain_edits(
+        renderer: Arc<Renderer>,
+        city_textures: Arc<CityTextures>,
+        params: CityParams,
+        (stop_flag, phases, slot): (Arc<AtomicBool>, Arc<Vec<AtomicU8>>, usize),
+        terrain_tiles: SharedTerrain,
+    ) {
+        profiling::register_thread!();
+        let phase = &phases[slot];
+        BuildPhase::Waiting.set(phase);
+        let interval = Duration::from_secs_f32(1.0 / params.terrain_edits);
+        let mut rng = Rng::new(params.seed);
+        let mut edited = 0; // terrain tiles re-uploaded since last report
+        let mut last_report = Instant::now();
+        let mut next_edit = Instant::now();
+        while !stop_flag.load(Ordering::Relaxed) {
+            let mut tiles = terrain_tiles.lock().unwrap();
+            if !tiles.is_empty() {
+                profiling::scope!("Terrain edits");
+                BuildPhase::TerrainEdit.set(phase);
+                terrain::edit_random_tile(
+                    &renderer,
+                    &city_textures.style.terrain,
+                    &mut tiles,
+                    &city_textures.ground,
+                    &mut rng,
+                );
+                edited += 1;
+                BuildPhase::Waiting.set(phase);
+            }
+            drop(tiles);
+            if last_report.elapsed() >= Duration::from_secs(10) {
+                if edited > 0 {
+                    stats::op_stats().report(&format!("Terrain edits of {} tiles", edited));
                 }
+                edited = 0;
+                last_report = Instant::now();
             }
+            next_edit += interval;
+            match next_edit.checked_duration_since(Instant::now()) {
+                Some(wait) => std::thread::sleep(wait),
+                None => next_edit = Instant::now(), // fell behind, do not try to catch up
+            }
+        }
+        BuildPhase::Stopped.set(phase);
+    }
+}
+
+//
+//  Level of detail
+//
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Lod {
+    Full,  // every wall section, floor, and ceiling
+    Shell, // outer walls merged into one mesh, plus roof
+    Box,   // one box with a facade texture
+}
+
+impl Lod {
+    /// Level of detail at this distance from the camera.
+    fn for_distance(lod_distances: Option<(f32, f32)>, distance: f32) -> Lod {
+        match lod_distances {
+            None => Lod::Full,
+            Some((near, _)) if distance < near => Lod::Full,
+            Some((_, far)) if distance < far => Lod::Shell,
+            Some(_) => Lod::Box,
+        }
+    }
+}
+
+/// One building, with the objects for its current level of detail.
+struct Building {
+    pos: Vec3,                  // position of corner
+    center: Vec3,               // center, for distance from camera
+    lod: Lod,                   // current level of detail
+    objects: Vec<SceneObject>, // objects which keep the building alive
+    props: Vec<SceneObject>,   // foundation, trees and street furniture, same at all LODs
+    lights: Vec<Light>,        // window and street lamp lights, same at all LODs
+}
+
+impl Building {
+    /// Number of objects, at the current level of detail.
+    fn object_count(&self) -> usize {
+        self.objects.len() + self.props.len()
+    }
+
+    /// Drop all objects and lights, one at a time, timing each drop.
+    fn delete(self) {
+        for object in self.objects.into_iter().chain(self.props) {
+            stats::timed(Op::DropObject, || drop(object));
+        }
+        for light in self.lights {
+            stats::timed(Op::DropLight, || drop(light));
         }
     }
 }
 
+/// One cell of the streamed city: a building on its own patch of ground.
+struct Cell {
+    building: Building,         // the building
+    ground: Vec<SceneObject>,  // ground under it, and streets if any
+}
+
+impl Cell {
+    /// Drop all objects, one at a time, timing each drop.
+    fn delete(self) {
+        self.building.delete();
+        for object in self.ground {
+            stats::timed(Op::DropObject, || drop(object));
+        }
+    }
+}
+
+/// Cells are dropped when this much farther away than the streaming radius.
+const STREAM_HYSTERESIS: f32 = 1.1;
+/// Reprioritize pending uploads when the camera has moved this far, in meters,
+const REPRIORITIZE_DISTANCE: f32 = 2.0;
+/// or turned more than this, as cosine of angle (about 10 degrees).
+const REPRIORITIZE_COS_ANGLE: f32 = 0.985;
+
+/// Center of a cell, at ground level.
+fn cell_center(key: CellKey, spacing: f32) -> Vec3 {
+    Vec3::new(
+        (key.0 as f32 + 0.5) * spacing,
+        0.0,
+        (key.1 as f32 + 0.5) * spacing,
+    )
+}
+
+/// Horizontal distance from camera to center of cell.
+fn cell_distance(key: CellKey, spacing: f32, camera_pos: Vec3) -> f32 {
+    let center = cell_center(key, spacing);
+    Vec3::new(center.x - camera_pos.x, 0.0, center.z - camera_pos.z).length()
+}
+
+/// All cells within radius of the camera, in row order.
+fn cells_in_radius(camera_pos: Vec3, radius: f32, spacing: f32) -> Vec<CellKey> {
+    let min_i = ((camera_pos.x - radius) / spacing).floor() as i32;
+    let max_i = ((camera_pos.x + radius) / spacing).ceil() as i32;
+    let min_j = ((camera_pos.z - radius) / spacing).floor() as i32;
+    let max_j = ((camera_pos.z + radius) / spacing).ceil() as i32;
+    (min_i..=max_i)
+        .flat_map(|i| (min_j..=max_j).map(move |j| (i, j)))
+        .filter(|key| cell_distance(*key, spacing, camera_pos) <= radius)
+        .collect()
+}
+
+/// Draw one cell of the streamed city.
+//  The building is centered on its lot. With streets, the lot is
+//  the low corner of the cell, and the streets take the rest.
+fn draw_cell(
+    renderer: &Arc<Renderer>,
+    key: CellKey,
+    (spacing, streets): (f32, bool), // cell spacing, and whether to draw streets
+    wall_specs: &[(&[WallKind], &[WallKind])],
+    textures: &CityTextures,
+    choose_lod: &dyn Fn(Vec3) -> Lod,
+) -> Cell {
+    profiling::scope!("Add cell");
+    let corner = Vec3::new(key.0 as f32 * spacing, 0.0, key.1 as f32 * spacing);
+    let lot_size = if streets { BLDG_SPACING } else { spacing };
+    let lot_center = corner + Vec3::new(lot_size, 0.0, lot_size) * 0.5;
+    let extent = building_extent(wall_specs);
+    let pos = lot_center - Vec3::new(extent.x, 0.0, extent.z) * 0.5; // building centered on lot
+    let center = lot_center + Vec3::new(0.0, extent.y * 0.5, 0.0); // as LOD updates measure it
+    let lod = choose_lod(center);
+    let ground = if streets {
+        draw_street_cell(renderer, corner, textures)
+    } else {
+        vec![solids::create_simple_block(
+            renderer,
+            Vec3::new(lot_size, 0.5, lot_size),
+            Vec3::new(0.0, -0.25, 0.0), // ground surface is at Y=0.0
+            lot_center,
+            Quat::IDENTITY,
+            &textures.ground,
+        )]
+    };
+    let (props, lamps) = draw_props(
+        renderer,
+        corner,
+        (spacing, streets),
+        (pos - corner, pos - corner + extent),
+        textures,
+    );
+    Cell {
+        building: Building {
+            pos,
+            center,
+            lod,
+            objects: draw_building_lod(renderer, lod, wall_specs, pos, textures),
+            props,
+            lights: draw_lights(renderer, wall_specs, pos, &lamps, textures),
+        },
+        ground,
+    }
+}
+
+/// Number of objects in a set of buildings.
+fn object_count(buildings: &[Building]) -> usize {
+    buildings.iter().map(Building::object_count).sum()
+}
+
+/// All the objects of some buildings, for changing in place.
+fn scene_objects<'a>(
+    buildings: impl Iterator<Item = &'a mut Building>,
+) -> Vec<&'a mut SceneObject> {
+    buildings
+        .flat_map(|b| b.objects.iter_mut().chain(b.props.iter_mut()))
+        .collect()
+}
+
+/// Redraw buildings whose level of detail has changed. Returns number of buildings redrawn.
+fn update_lods(
+    renderer: &Arc<Renderer>,
+    buildings: &mut [Building],
+    choose_lod: &dyn Fn(Vec3) -> Lod,
+    wall_specs: &[(&[WallKind], &[WallKind])],
+    textures: &CityTextures,
+) -> usize {
+    let mut swapped = 0;
+    for building in buildings.iter_mut() {
+        let lod = choose_lod(building.center);
+        if lod == building.lod {
+            continue;
+        }
+        //  Add the new version before dropping the old one, so there is no gap.
+        let objects = draw_building_lod(renderer, lod, wall_specs, building.pos, textures);
+        for object in std::mem::replace(&mut building.objects, objects) {
+            stats::timed(Op::DropObject, || drop(object));
+        }
+        building.lod = lod;
+        swapped += 1;
+    }
+    swapped
+}
+
 //
 //  WallKind
 //
@@ -240,8 +1000,9 @@ enum WallKind {
 
 /// Building textures
 pub struct TextureSetRgba {
-    albedo: RgbaImage, // albedo image
-    normal: RgbaImage, // normal image
+    albedo: RgbaImage,       // albedo image
+    normal: RgbaImage,       // normal image
+    aomr: Option<RgbaImage>, // ambient occlusion, roughness, metallic image, if any
     texture_scale: f32,
 }
 
@@ -249,15 +1010,40 @@ type TextureSetRgbaMap = HashMap<String, TextureSetRgba>;
 
 impl TextureSetRgba {
     //  Make a map with all the textures as Rgba images.
-    pub fn new_map(dir: &str, textures: &[(String, String, String, f32)]) -> TextureSetRgbaMap {
+    //  With aomr, each set also gets an ambient occlusion, metallic and
+    //  roughness map. One shipped next to the albedo file, named with
+    //  "_aomr" for "_albedo", is used if present. Otherwise one is made
+    //  from the albedo and normal images.
+    pub fn new_map(
+        dir: &str,
+        textures: &[(String, String, String, f32)],
+        aomr: bool,
+    ) -> TextureSetRgbaMap {
         //  Read textures, save all RGBAs
         let mut output = HashMap::new();
         for (name, albedo_filename, normal_filename, texture_scale) in textures {
+            let albedo =
+                solids::read_texture(format!("{}/{}", dir, albedo_filename).as_str()).unwrap();
+            let normal =
+                solids::read_texture(format!("{}/{}", dir, normal_filename).as_str()).unwrap();
+            let aomr = if aomr {
+                //  A shipped map is named like the albedo file. Without "_albedo" in
+                //  the name there is no telling what it would be called, so make one.
+                let shipped = Some(albedo_filename)
+                    .filter(|name| name.contains("_albedo"))
+                    .map(|name| format!("{}/{}", dir, name.replace("_albedo", "_aomr")))
+                    .filter(|name| std::path::Path::new(name).exists());
+                match shipped {
+                    Some(aomr_filename) => Some(solids::read_texture(&aomr_filename).unwrap()),
+                    None => Some(make_aomr_image(&albedo, &normal)),
+                }
+            } else {
+                None
+            };
             let texture_set = TextureSetRgba {
-                albedo: solids::read_texture(format!("{}/{}", dir, albedo_filename).as_str())
-                    .unwrap(),
-                normal: solids::read_texture(format!("{}/{}", dir, normal_filename).as_str())
-                    .unwrap(),
+                albedo,
+                normal,
+                aomr,
                 texture_scale: *texture_scale,
             };
             output.insert(name.clone(), texture_set);
@@ -265,39 +1051,163 @@ impl TextureSetRgba {
         output
     }
 }
-pub type TextureSet = (Texture2DHandle, Texture2DHandle, f32); // albedo, normal, scale
 /// The textures we need for our little city.
 pub struct CityTextures {
-    stone: TextureSet,   // used for columns
-    brick: TextureSet,   // used for walls
-    floor: TextureSet,   // used for floors
-    ceiling: TextureSet, // used for ceilings
-    roof: TextureSet,    // used for roofs
-    ground: TextureSet,  // used for ground
+    stone: TextureSet,        // used for columns
+    brick: TextureSet,        // used for walls
+    floor: TextureSet,        // used for floors
+    ceiling: TextureSet,      // used for ceilings
+    roof: TextureSet,         // used for roofs
+    ground: TextureSet,       // used for ground and road surfaces
+    facade: TextureSet,       // used for distant buildings
+    marble: TextureSet,       // used for tables and shelves
+    green_marble: TextureSet, // used for chairs, lamps and books
+    glass: TextureSet,        // used for windows and glass doors
+    style: BuildingStyle,     // details beyond textures
+    lights: Arc<LightBudget>, // window and street lamp lights, shared by all threads
+}
+
+/// Building details, beyond textures.
+#[derive(Debug, Copy, Clone)]
+pub struct BuildingStyle {
+    pub tessellation: u32,           // sides of round columns, domes and railings. 0 for square everything.
+    pub furniture_density: f32,      // fraction of floor slots furnished, 0 for empty
+    pub scatter_density: f32,        // trees and street furniture, 0 for none
+    pub terrain: Terrain,            // ground the buildings stand on
+    pub glass: Option<Transparency>, // glass panes in windows and doors, if any
+    pub variety: Variety,            // how materials vary from object to object
+    pub seed: u64,                   // for furniture and prop placement
 }
 
+/// Sides of round furniture parts when the style has no round shapes.
+const FURNITURE_TESSELLATION: u32 = 12;
+
 impl CityTextures {
-    //  Make a new set of textures from an Rgba.
+    //  Make a new set of textures from the Rgbas in the shared state.
     //  This duplicates the actual bitmaps, on purpose, to increase texture usage for load testing.
-    pub fn new_from_map(renderer: &Arc<Renderer>, rgbas: &TextureSetRgbaMap) -> CityTextures {
-        let make_textures = |label: &str, item: &TextureSetRgba| {
-            (
-                solids::create_texture_from_rgba(renderer, label, &item.albedo),
-                solids::create_texture_from_rgba(renderer, label, &item.normal),
-                item.texture_scale,
-            )
+    pub fn new_from_map(
+        renderer: &Arc<Renderer>,
+        state: &CityState,
+        sharing: Sharing,
+        style: BuildingStyle,
+    ) -> CityTextures {
+        let rgbas = &state.textures;
+        let cache = Arc::new(AssetCache::new(sharing, style.variety)); // shared by all texture sets
+        let make_textures = |label: &str, item: &TextureSetRgba| TextureSet {
+            name: label.to_string(),
+            albedo: solids::create_texture_from_rgba(renderer, label, &item.albedo),
+            normal: solids::create_texture_from_rgba(renderer, label, &item.normal),
+            aomr: item
+                .aomr
+                .as_ref()
+                .map(|aomr| solids::create_data_texture_from_rgba(renderer, label, aomr)),
+            scale: item.texture_scale,
+            cache: Arc::clone(&cache),
+            transparency: Transparency::Opaque,
         };
         let get_textures = |key| make_textures(key, rgbas.get(key).unwrap());
+        let brick = rgbas.get("brick").unwrap();
+        let facade = TextureSetRgba {
+            albedo: make_facade_image(&brick.albedo),
+            normal: brick.normal.clone(),
+            aomr: None, // window openings would need their own
+            texture_scale: FACADE_TEXTURE_SCALE,
+        };
+        let glass = TextureSetRgba {
+            albedo: make_glass_image(),
+            normal: RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255])), // flat
+            aomr: None,
+            texture_scale: GLASS_TEXTURE_SCALE,
+        };
         CityTextures {
             stone: get_textures("stone"),
             brick: get_textures("brick"),
             floor: get_textures("floor"),
             ceiling: get_textures("ceiling"),
             roof: get_textures("roof"),
-            ground: get_textures("roof"),
+            ground: get_textures("ground"),
+            facade: make_textures("facade", &facade),
+            marble: get_textures("marble"),
+            green_marble: get_textures("green_marble"),
+            glass: TextureSet {
+                transparency: style.glass.unwrap_or(Transparency::Blend),
+                ..make_textures("glass", &glass)
+            },
+            style,
+            lights: Arc::clone(&state.lights),
         }
     }
 }
+
+/// Facade texture covers 3 bays by 2 stories, 6m x 6m.
+const FACADE_TEXTURE_SCALE: f32 = 0.25 / 3.0;
+
+/// Glass texture covers one pane, 0.5m x 0.5m.
+const GLASS_TEXTURE_SCALE: f32 = 1.0;
+
+/// Make a glass image. One pane, translucent, framed by an opaque muntin.
+//  Blended, the panes are tinted glass. Cut out, only the muntins are left.
+fn make_glass_image() -> RgbaImage {
+    const SIZE: u32 = 64;
+    const MUNTIN: u32 = 2; // pixels, on each edge, so 4 between panes
+    const PANE_COLOR: image::Rgba<u8> = image::Rgba([170, 200, 210, 70]); // pale blue-green
+    const MUNTIN_COLOR: image::Rgba<u8> = image::Rgba([60, 60, 60, 255]); // dark paint
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        let edge = |v: u32| !(MUNTIN..SIZE - MUNTIN).contains(&v);
+        if edge(x) || edge(y) {
+            MUNTIN_COLOR
+        } else {
+            PANE_COLOR
+        }
+    })
+}
+
+/// Make an ambient occlusion, roughness and metallic image from albedo and normal images.
+//  Channels as glTF: red is occlusion, green roughness, blue metallic.
+//  Where the normal map tilts, as in the gaps between bricks, and where the
+//  albedo is darker than average, the surface is occluded and rougher.
+//  Roughness and metallic are relative, 0.6 to 1 and 1, since the material's
+//  factors multiply them and set the overall levels.
+fn make_aomr_image(albedo: &RgbaImage, normal: &RgbaImage) -> RgbaImage {
+    let luma =
+        |p: &image::Rgba<u8>| (0.3 * p[0] as f32 + 0.59 * p[1] as f32 + 0.11 * p[2] as f32) / 255.0;
+    let mean_luma =
+        albedo.pixels().map(luma).sum::<f32>() / (albedo.width() * albedo.height()).max(1) as f32;
+    RgbaImage::from_fn(albedo.width(), albedo.height(), |x, y| {
+        //  Normal image may be a different size.
+        let n = normal.get_pixel(
+            x * normal.width() / albedo.width(),
+            y * normal.height() / albedo.height(),
+        );
+        let flat = n[2] as f32 / 255.0; // 1 facing straight out
+        let shade = (luma(albedo.get_pixel(x, y)) / mean_luma.max(0.01)).min(1.0);
+        let ao = (0.5 + 0.5 * flat) * (0.7 + 0.3 * shade);
+        let roughness = 1.0 - 0.4 * flat * shade;
+        image::Rgba([(ao * 255.0) as u8, (roughness * 255.0) as u8, 255, 255])
+    })
+}
+
+/// Make a facade image for distant buildings, from the brick image.
+//  Brick, with dark window openings where the window bays would be.
+//  Matches the standard wall bay, 2m wide with a 0.4m column, and the 3m story
+//  with a window from 0.75m to 2.25m.
+fn make_facade_image(brick: &RgbaImage) -> RgbaImage {
+    const PIXELS_PER_METER: u32 = 64;
+    const SIZE: u32 = PIXELS_PER_METER * 6;
+    const WINDOW_COLOR: image::Rgba<u8> = image::Rgba([40, 48, 60, 255]); // dark bluish glass
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        let bay_x = (x % (PIXELS_PER_METER * 2)) as f32 / PIXELS_PER_METER as f32; // meters into bay
+        let story_y = (y % (PIXELS_PER_METER * 3)) as f32 / PIXELS_PER_METER as f32; // meters into story
+        if bay_x >= 0.4 && (0.75..2.25).contains(&story_y) {
+            WINDOW_COLOR
+        } else {
+            //  Brick texture repeats every 2m.
+            let u = (x % (PIXELS_PER_METER * 2)) * brick.width() / (PIXELS_PER_METER * 2);
+            let v = (y % (PIXELS_PER_METER * 2)) * brick.height() / (PIXELS_PER_METER * 2);
+            *brick.get_pixel(u, v)
+        }
+    })
+}
 //
 //  Draw functions for various objects
 //
@@ -305,38 +1215,508 @@ impl CityTextures {
 //  Standard buildings, centered on the origin.
 fn draw_building_grid(
     renderer: &Arc<Renderer>,
-    bldg_rows: core::ops::Range<usize>,
+    bldg_rows: &[usize],                       // rows to draw
+    (grid_size, spacing, streets): (usize, f32, bool), // grid rows and columns, cell spacing, streets
     wall_specs: &[(&[WallKind], &[WallKind])], // array of stories, going upwar
     city_textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+    choose_lod: &dyn Fn(Vec3) -> Lod,          // level of detail for building centered here
+) -> Vec<Building> {
     //  Multiple  buildings
-    const BLDG_ROWS: usize = 25;
-    const BLDG_SPACING: f32 = 10.0;
-    const WALL_WIDTH: f32 = 2.0; // one wall bay
-    const STORY_HEIGHT: f32 = 3.0;
-    let mut objects = Vec::new();
-    let bldg_initialpos = Vec3::new(
-        -BLDG_SPACING * (BLDG_ROWS as f32) * 0.5,
-        0.0,
-        -BLDG_SPACING * (BLDG_ROWS as f32) * 0.5,
-    ); // center array
-    for i in bldg_rows {
-        for j in 0..BLDG_ROWS {
-            let story_pos = Vec3::new((i as f32) * BLDG_SPACING, 0.0, (j as f32) * BLDG_SPACING)
-                + bldg_initialpos;
-            objects.extend(draw_building(
+    let mut buildings = Vec::new();
+    for &i in bldg_rows {
+        for j in 0..grid_size {
+            let extent = building_extent(wall_specs);
+            let (story_pos, foundation) = draw_foundation(
+                renderer,
+                grid_corner(i, j, grid_size, spacing),
+                extent,
+                city_textures,
+            );
+            let center = story_pos + extent * 0.5;
+            let lod = choose_lod(center);
+            let (props, lamps) = draw_props(
                 renderer,
-                wall_specs,
-                Vec3::new(WALL_WIDTH, STORY_HEIGHT, 0.2),
                 story_pos,
-                Quat::IDENTITY,
+                (spacing, streets),
+                (Vec3::ZERO, extent),
                 city_textures,
+            );
+            buildings.push(Building {
+                pos: story_pos,
+                center,
+                lod,
+                objects: draw_building_lod(renderer, lod, wall_specs, story_pos, city_textures),
+                props: foundation.into_iter().chain(props).collect(),
+                lights: draw_lights(renderer, wall_specs, story_pos, &lamps, city_textures),
+            });
+        }
+    }
+    buildings
+}
+
+/// Set a building on the terrain, on a foundation down to the lowest ground under it.
+//  Returns the corner, raised to the highest ground under the building,
+//  and the foundation. Nothing to do on flat ground.
+fn draw_foundation(
+    renderer: &Arc<Renderer>,
+    corner: Vec3,
+    extent: Vec3,
+    textures: &CityTextures,
+) -> (Vec3, Option<SceneObject>) {
+    const FOUNDATION_DEPTH: f32 = 0.5; // below the lowest ground
+    let terrain = &textures.style.terrain;
+    if terrain.is_flat() {
+        return (corner, None);
+    }
+    let low = Vec2::new(corner.x, corner.z);
+    let (lowest, highest) = terrain.range(low, low + Vec2::new(extent.x, extent.z));
+    let pos = corner + Vec3::new(0.0, highest, 0.0);
+    let depth = highest - lowest + FOUNDATION_DEPTH;
+    let foundation = solids::create_simple_block(
+        renderer,
+        Vec3::new(extent.x, depth, extent.z),
+        Vec3::new(extent.x * 0.5, -depth * 0.5, extent.z * 0.5), // top at floor level
+        pos,
+        Quat::IDENTITY,
+        &textures.stone,
+    );
+    (pos, Some(foundation))
+}
+
+/// Low corner of a cell of the building grid, which is centered on the origin.
+fn grid_corner(i: usize, j: usize, grid_size: usize, spacing: f32) -> Vec3 {
+    let initial_pos = -spacing * (grid_size as f32) * 0.5;
+    Vec3::new(
+        initial_pos + i as f32 * spacing,
+        0.0,
+        initial_pos + j as f32 * spacing,
+    )
+}
+
+/// Roads for animated vehicles, over the area of the building grid.
+//  Along the streets if there are any, else along the gaps between rows of
+//  buildings, which are only wide enough for traffic in the X direction.
+//  When streaming, the same area, around the origin.
+fn traffic_roads(params: &CityParams, extent: Vec3) -> Vec<Road> {
+    const LANE_OFFSET: f32 = 1.0; // lane center from road center
+    let rows = params.bldg_rows;
+    let spacing = params.cell_spacing();
+    let streaming = params.stream_radius.is_some();
+    let first = if streaming {
+        let start = -((rows / 2) as f32) * spacing; // on a cell boundary
+        Vec3::new(start, 0.0, start)
+    } else {
+        grid_corner(0, 0, rows, spacing)
+    };
+    let length = rows as f32 * spacing;
+    //  Center line, from the low corner of a cell, and how far out signs go.
+    let (center, curb) = if params.streets {
+        (
+            BLDG_SPACING + STREET_WIDTH * 0.5,
+            (STREET_WIDTH - SIDEWALK_WIDTH) * 0.5, // middle of sidewalk
+        )
+    } else {
+        //  Buildings are at the low corner of their cells, or centered when streaming.
+        let gap = spacing - extent.z;
+        let building = if streaming { gap * 0.5 } else { 0.0 };
+        (building + extent.z + gap * 0.5, gap * 0.5 - 1.0)
+    };
+    let mut roads = Vec::new();
+    for n in 0..rows {
+        let across = n as f32 * spacing + center;
+        roads.push(Road {
+            start: first + Vec3::new(0.0, 0.0, across),
+            end: first + Vec3::new(length, 0.0, across),
+            lane: LANE_OFFSET,
+            curb,
+        });
+        if params.streets {
+            roads.push(Road {
+                start: first + Vec3::new(across, 0.0, 0.0),
+                end: first + Vec3::new(across, 0.0, length),
+                lane: LANE_OFFSET,
+                curb,
+            });
+        }
+    }
+    roads
+}
+
+/// Draw streets for rows of the building grid.
+fn draw_street_grid(
+    renderer: &Arc<Renderer>,
+    bldg_rows: &[usize],
+    grid_size: usize,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    profiling::scope!("Draw streets");
+    let spacing = BLDG_SPACING + STREET_WIDTH;
+    let mut objects = Vec::new();
+    for &i in bldg_rows {
+        for j in 0..grid_size {
+            objects.extend(draw_street_cell(
+                renderer,
+                grid_corner(i, j, grid_size, spacing),
+                textures,
             ));
         }
     }
     objects
 }
 
+/// Draw the ground and streets of one cell.
+//  The lot is at the low corner of the cell. A street runs along its +X side,
+//  another along its +Z side, and they meet at an intersection at the far
+//  corner. Adjacent cells supply the streets on the other two sides, so cells
+//  tile the plane. Each street has a cobblestone roadway, a raised sidewalk
+//  and a stone curb on each side, and crosswalk stripes at both ends.
+fn draw_street_cell(
+    renderer: &Arc<Renderer>,
+    corner: Vec3,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    const SLAB: f32 = 0.5; // thickness of ground slabs
+    let mut objects = Vec::new();
+    let mut block = |size: Vec3, center: Vec3, texture_set: &TextureSet| {
+        objects.push(solids::create_simple_block(
+            renderer,
+            size,
+            Vec3::ZERO,
+            corner + center,
+            Quat::IDENTITY,
+            texture_set,
+        ));
+    };
+    //  Lot
+    block(
+        Vec3::new(BLDG_SPACING, SLAB, BLDG_SPACING),
+        Vec3::new(BLDG_SPACING * 0.5, -SLAB * 0.5, BLDG_SPACING * 0.5),
+        &textures.ground,
+    );
+    //  The two streets. Each is described in (across, along) coordinates,
+    //  mapped to X and Z by place().
+    for along_x in [false, true] {
+        let place = |across: f32, y: f32, along: f32| {
+            if along_x {
+                Vec3::new(along, y, BLDG_SPACING + across)
+            } else {
+                Vec3::new(BLDG_SPACING + across, y, along)
+            }
+        };
+        let size = |across: f32, y: f32, along: f32| {
+            if along_x {
+                Vec3::new(along, y, across)
+            } else {
+                Vec3::new(across, y, along)
+            }
+        };
+        let roadway = STREET_WIDTH - 2.0 * SIDEWALK_WIDTH;
+        //  Roadway
+        block(
+            size(roadway, SLAB, BLDG_SPACING),
+            place(STREET_WIDTH * 0.5, -SLAB * 0.5, BLDG_SPACING * 0.5),
+            &textures.ground,
+        );
+        //  Sidewalks and curbs, one on each side.
+        let paving = SIDEWALK_WIDTH - CURB_WIDTH; // sidewalk without the curb
+        for (sidewalk_center, curb_center) in [
+            (paving * 0.5, paving + CURB_WIDTH * 0.5),
+            (
+                STREET_WIDTH - paving * 0.5,
+                STREET_WIDTH - paving - CURB_WIDTH * 0.5,
+            ),
+        ] {
+            block(
+                size(paving, SLAB + CURB_HEIGHT, BLDG_SPACING),
+                place(sidewalk_center, (CURB_HEIGHT - SLAB) * 0.5, BLDG_SPACING * 0.5),
+                &textures.floor,
+            );
+            block(
+                size(CURB_WIDTH, SLAB + CURB_HEIGHT, BLDG_SPACING),
+                place(curb_center, (CURB_HEIGHT - SLAB) * 0.5, BLDG_SPACING * 0.5),
+                &textures.stone,
+            );
+        }
+        //  Crosswalk stripes at both ends of the roadway.
+        let stripe_count = (roadway / (CROSSWALK_STRIPE_WIDTH * 2.0)) as usize;
+        for end in [CROSSWALK_LENGTH * 0.5, BLDG_SPACING - CROSSWALK_LENGTH * 0.5] {
+            for n in 0..stripe_count {
+                block(
+                    size(CROSSWALK_STRIPE_WIDTH, 0.02, CROSSWALK_LENGTH),
+                    place(
+                        SIDEWALK_WIDTH + (n as f32 * 2.0 + 1.0) * CROSSWALK_STRIPE_WIDTH,
+                        0.0, // half above the roadway
+                        end,
+                    ),
+                    &textures.stone,
+                );
+            }
+        }
+    }
+    //  Intersection. Roadway, with a square of sidewalk at each corner.
+    let intersection_center = BLDG_SPACING + STREET_WIDTH * 0.5;
+    block(
+        Vec3::new(STREET_WIDTH, SLAB, STREET_WIDTH),
+        Vec3::new(intersection_center, -SLAB * 0.5, intersection_center),
+        &textures.ground,
+    );
+    let corner_offset = (STREET_WIDTH - SIDEWALK_WIDTH) * 0.5;
+    for (dx, dz) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+        block(
+            Vec3::new(SIDEWALK_WIDTH, SLAB + CURB_HEIGHT, SIDEWALK_WIDTH),
+            Vec3::new(
+                intersection_center + dx * corner_offset,
+                (CURB_HEIGHT - SLAB) * 0.5,
+                intersection_center + dz * corner_offset,
+            ),
+            &textures.stone,
+        );
+    }
+    objects
+}
+
+/// Scatter trees and street furniture over one cell.
+//  Street furniture goes on the sidewalks, trees on the sidewalks and on
+//  open ground around the building. Points are kept half a spacing in from
+//  the cell edges, so spacing holds across cells too. Footprint is the
+//  building, relative to the cell corner. Also returns where the lamp globes are.
+fn draw_props(
+    renderer: &Arc<Renderer>,
+    corner: Vec3,
+    (spacing, streets): (f32, bool), // cell spacing, and whether there are streets
+    (low, high): (Vec3, Vec3),       // building footprint
+    textures: &CityTextures,
+) -> (Vec<SceneObject>, Vec<Vec3>) {
+    const BUILDING_MARGIN: f32 = 1.0; // keep trees this far from walls
+    const SCATTER_SALT: u64 = 0x5ca7_7e25; // so props do not repeat furniture choices
+    let style = &textures.style;
+    if style.scatter_density <= 0.0 {
+        return (Vec::new(), Vec::new());
+    }
+    let scatterer = Scatterer {
+        renderer,
+        density: style.scatter_density,
+        tessellation: if style.tessellation > 0 {
+            style.tessellation
+        } else {
+            FURNITURE_TESSELLATION
+        },
+        bark: &textures.floor,
+        leaves: &textures.green_marble,
+        metal: &textures.stone,
+        trim: &textures.marble,
+    };
+    let lot_size = if streets { BLDG_SPACING } else { spacing };
+    let paving = SIDEWALK_WIDTH - CURB_WIDTH;
+    //  Sidewalk on one side of a street, given position across it.
+    //  The near side faces +across, the far side -across.
+    let sidewalk = |across: f32, toward_road: f32, away_from_road: f32| {
+        if across < paving {
+            Some(Ground::Sidewalk {
+                height: CURB_HEIGHT,
+                yaw: toward_road,
+            })
+        } else if across > STREET_WIDTH - paving {
+            Some(Ground::Sidewalk {
+                height: CURB_HEIGHT,
+                yaw: away_from_road,
+            })
+        } else {
+            None // roadway or curb
+        }
+    };
+    let ground = |p: Vec2| {
+        let in_lot = |v: f32| v < lot_size;
+        match (in_lot(p.x), in_lot(p.y)) {
+            (true, true) => {
+                let near_building = p.x > low.x - BUILDING_MARGIN
+                    && p.x < high.x + BUILDING_MARGIN
+                    && p.y > low.z - BUILDING_MARGIN
+                    && p.y < high.z + BUILDING_MARGIN;
+                if near_building {
+                    None
+                } else {
+                    Some(Ground::Yard {
+                        height: style.terrain.height_at(Vec2::new(corner.x, corner.z) + p)
+                            - corner.y,
+                    })
+                }
+            }
+            (false, true) => sidewalk(p.x - lot_size, PI * 0.5, -PI * 0.5), // street along Z
+            (true, false) => sidewalk(p.y - lot_size, 0.0, PI),             // street along X
+            (false, false) => None,                                         // intersection
+        }
+    };
+    let margin = scatterer.spacing() * 0.5;
+    let mut rng = Rng::at_position(style.seed ^ SCATTER_SALT, corner);
+    scatterer.scatter(
+        (Vec2::splat(margin), Vec2::splat(spacing - margin)),
+        corner,
+        &ground,
+        &mut rng,
+    )
+}
+
+/// Add lights for a building: its street lamps, then some of its windows.
+//  Only as many as the light budget allows. Which windows are lit is seeded
+//  by position, so the same building is always lit the same way.
+fn draw_lights(
+    renderer: &Arc<Renderer>,
+    wall_specs: &[(&[WallKind], &[WallKind])],
+    pos: Vec3,
+    lamps: &[Vec3],
+    textures: &CityTextures,
+) -> Vec<Light> {
+    const LIT_FRACTION: f32 = 0.3; // of windows
+    const WINDOW_INSET: f32 = 0.5; // light is this far inside the window
+    const LIGHT_SALT: u64 = 0x119_4752; // so lit windows do not follow furniture choices
+    if textures.lights.is_used_up() {
+        return Vec::new(); // no lights, or none left
+    }
+    let mut places: Vec<(Vec3, LightKind)> = lamps
+        .iter()
+        .map(|&lamp| (lamp, LightKind::StreetLamp))
+        .collect();
+    let mut rng = Rng::at_position(textures.style.seed ^ LIGHT_SALT, pos);
+    for (n, (front, side)) in wall_specs.iter().enumerate() {
+        let front_width = front.len() as f32 * WALL_WIDTH;
+        let side_width = side.len() as f32 * WALL_WIDTH;
+        //  Faces as in draw_one_story: start corner, rotation, wall kinds.
+        let faces = [
+            (Vec3::ZERO, 0.0, front),
+            (Vec3::new(front_width, 0.0, 0.0), -PI * 0.5, side),
+            (Vec3::new(front_width, 0.0, side_width), -PI, front),
+            (Vec3::new(0.0, 0.0, side_width), -PI * 1.5, side),
+        ];
+        for (start, angle, kinds) in faces {
+            let rot = Quat::from_rotation_y(angle);
+            for (i, kind) in kinds.iter().enumerate() {
+                if matches!(kind, WallKind::Window) && rng.chance(LIT_FRACTION) {
+                    //  Middle of the opening, which is to the right of the column.
+                    let offset = Vec3::new(
+                        (i as f32 + 0.6) * WALL_WIDTH,
+                        (n as f32 + 0.5) * STORY_HEIGHT,
+                        WINDOW_INSET,
+                    );
+                    places.push((pos + start + rot * offset, LightKind::Window));
+                }
+            }
+        }
+    }
+    lights::add_lights(renderer, &textures.lights, &places)
+}
+
+//  Street dimensions
+const STREET_WIDTH: f32 = 8.0; // curb to curb plus both sidewalks
+const SIDEWALK_WIDTH: f32 = 2.0; // including curb
+const CURB_WIDTH: f32 = 0.2;
+const CURB_HEIGHT: f32 = 0.15;
+const CROSSWALK_LENGTH: f32 = 2.5;
+const CROSSWALK_STRIPE_WIDTH: f32 = 0.5;
+
+//  Standard building dimensions
+const BLDG_SPACING: f32 = 10.0; // building grid spacing, or lot size if there are streets
+const WALL_WIDTH: f32 = 2.0; // one wall bay
+const STORY_HEIGHT: f32 = 3.0;
+const WALL_THICKNESS: f32 = 0.2;
+
+/// Size of a standard building, front width, height, and side width.
+fn building_extent(wall_specs: &[(&[WallKind], &[WallKind])]) -> Vec3 {
+    match wall_specs.last() {
+        Some((front, side)) => Vec3::new(
+            front.len() as f32 * WALL_WIDTH,
+            wall_specs.len() as f32 * STORY_HEIGHT,
+            side.len() as f32 * WALL_WIDTH,
+        ),
+        None => Vec3::ZERO,
+    }
+}
+
+/// Draw a standard building at the requested level of detail.
+fn draw_building_lod(
+    renderer: &Arc<Renderer>,
+    lod: Lod,
+    wall_specs: &[(&[WallKind], &[WallKind])],
+    pos: Vec3,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    match lod {
+        Lod::Full => draw_building(
+            renderer,
+            wall_specs,
+            Vec3::new(WALL_WIDTH, STORY_HEIGHT, WALL_THICKNESS),
+            pos,
+            Quat::IDENTITY,
+            textures,
+        ),
+        Lod::Shell => draw_building_shell(
+            renderer,
+            building_extent(wall_specs),
+            WALL_THICKNESS,
+            pos,
+            Quat::IDENTITY,
+            textures,
+        ),
+        Lod::Box => draw_building_box(
+            renderer,
+            building_extent(wall_specs),
+            pos,
+            Quat::IDENTITY,
+            textures,
+        ),
+    }
+}
+
+/// Draw the outside of a building, with no openings and no interior.
+//  The four walls are merged into one mesh. The roof is separate, because
+//  it has a different texture.
+fn draw_building_shell(
+    renderer: &Arc<Renderer>,
+    extent: Vec3, // front width, height, side width
+    thickness: f32,
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    let (width, height, depth) = (extent[0], extent[1], extent[2]);
+    let walls = [
+        (Vec3::new(width, height, thickness), Vec3::new(width * 0.5, height * 0.5, 0.0)), // front
+        (Vec3::new(width, height, thickness), Vec3::new(width * 0.5, height * 0.5, depth)), // back
+        (Vec3::new(thickness, height, depth), Vec3::new(0.0, height * 0.5, depth * 0.5)), // left
+        (Vec3::new(thickness, height, depth), Vec3::new(width, height * 0.5, depth * 0.5)), // right
+    ];
+    vec![
+        solids::create_merged_block(renderer, &walls, pos, rot, &textures.brick),
+        solids::create_simple_block(
+            // roof
+            renderer,
+            Vec3::new(width + thickness, thickness * 0.5, depth + thickness),
+            Vec3::new(width * 0.5, height, depth * 0.5),
+            pos,
+            rot,
+            &textures.roof,
+        ),
+    ]
+}
+
+/// Draw a building as one box with a facade texture, for long distances.
+fn draw_building_box(
+    renderer: &Arc<Renderer>,
+    extent: Vec3, // front width, height, side width
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    vec![solids::create_simple_block(
+        renderer,
+        extent,
+        extent * 0.5, // base at zero
+        pos,
+        rot,
+        &textures.facade,
+    )]
+}
+
 //  Draw building
 //  The pattern in wall_specs determines the form of the building.
 //  Buildings are rectangular and consist of bays of windows, doors, or solid wall.
@@ -350,7 +1730,7 @@ fn draw_building(
     pos: Vec3,                                 // position
     rot: Quat,                                 // orientation
     textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+) -> Vec<SceneObject> {
     profiling::scope!("Add building");
     profiling::register_thread!();
     let width = size[0];
@@ -397,7 +1777,7 @@ fn draw_one_story(
     pos: Vec3,
     rot: Quat,
     textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+) -> Vec<SceneObject> {
     let width = size[0];
     let height = size[1];
     let (front, side) = wall_spec;
@@ -425,7 +1805,7 @@ fn draw_one_story(
                 let startpos = pos;
                 draw_one_face(startpos, itemoffset, Quat::IDENTITY, kind)
             })
-            .collect::<Vec<ObjectHandle>>(),
+            .collect::<Vec<SceneObject>>(),
     );
     //  Right side
     objects.extend(
@@ -436,7 +1816,7 @@ fn draw_one_story(
                 let startpos = pos + rot * Vec3::new(front_width, 0.0, 0.0);
                 draw_one_face(startpos, itemoffset, Quat::from_rotation_y(-PI * 0.5), kind)
             })
-            .collect::<Vec<ObjectHandle>>(),
+            .collect::<Vec<SceneObject>>(),
     );
     //  Back
     objects.extend(
@@ -448,7 +1828,7 @@ fn draw_one_story(
                 let startpos = pos + rot * Vec3::new(front_width, 0.0, side_width);
                 draw_one_face(startpos, itemoffset, Quat::from_rotation_y(-PI), kind)
             })
-            .collect::<Vec<ObjectHandle>>(),
+            .collect::<Vec<SceneObject>>(),
     );
     //  Left side
     objects.extend(
@@ -459,13 +1839,35 @@ fn draw_one_story(
                 let startpos = pos + rot * Vec3::new(0.0, 0.0, side_width);
                 draw_one_face(startpos, itemoffset, Quat::from_rotation_y(-PI * 1.5), kind)
             })
-            .collect::<Vec<ObjectHandle>>(),
+            .collect::<Vec<SceneObject>>(),
     );
     //  Floor and ceiling
     let floor_size = Vec3::new(front_width, 0.1, side_width);
     objects.extend(draw_floor_and_ceiling(
         renderer, height, floor_size, pos, rot, textures,
     ));
+    //  Furniture, if any. The same place always gets the same furniture.
+    let style = &textures.style;
+    if style.furniture_density > 0.0 {
+        let furnisher = Furnisher {
+            renderer,
+            density: style.furniture_density,
+            tessellation: if style.tessellation > 0 {
+                style.tessellation
+            } else {
+                FURNITURE_TESSELLATION
+            },
+            wood: &textures.marble,
+            trim: &textures.green_marble,
+        };
+        let mut rng = Rng::at_position(style.seed, pos);
+        objects.extend(furnisher.furnish(
+            Vec2::new(front_width, side_width),
+            pos,
+            rot,
+            &mut rng,
+        ));
+    }
     ////objects.extend(draw_roof(renderer, height, thickness, floor_size, pos, rot, textures));
     objects
 }
@@ -481,22 +1883,35 @@ fn draw_wall_section(
     pos: Vec3,
     rot: Quat,
     textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+) -> Vec<SceneObject> {
     //  Precompute wall info
     let width = size[0];
     let thickness = size[2];
     let height = size[1];
     let column_thickness = thickness * 2.0;
     let wall_width = width - column_thickness;
-    //  Draw column. Base of column is atop pos.
-    let mut objects = vec![solids::create_simple_block(
-        renderer,
-        Vec3::new(column_thickness, height, column_thickness), // size of column
-        Vec3::new(0.0, height / 2.0, 0.0),                     // base at zero
-        pos,
-        rot,
-        &textures.stone,
-    )];
+    //  Draw column. Base of column is atop pos. Round if the style has round shapes.
+    let tessellation = textures.style.tessellation;
+    let mut objects = vec![if tessellation > 0 {
+        solids::create_prim(
+            renderer,
+            &Prim::new(PrimShape::Cylinder, tessellation),
+            Vec3::new(column_thickness, height, column_thickness), // size of column
+            Vec3::new(0.0, height / 2.0, 0.0),                     // base at zero
+            pos,
+            rot,
+            &textures.stone,
+        )
+    } else {
+        solids::create_simple_block(
+            renderer,
+            Vec3::new(column_thickness, height, column_thickness), // size of column
+            Vec3::new(0.0, height / 2.0, 0.0),                     // base at zero
+            pos,
+            rot,
+            &textures.stone,
+        )
+    }];
     // Draw wall section
     match wall_kind {
         WallKind::Solid => {
@@ -526,6 +1941,19 @@ fn draw_wall_section(
                 rot,
                 &textures.brick,
             ));
+            //  Glass door, if any.
+            objects.extend(draw_glass_pane(
+                renderer,
+                Vec2::new(wall_width, opening_height),
+                Vec3::new(
+                    (column_thickness + wall_width) / 2.0,
+                    opening_height / 2.0,
+                    0.0,
+                ),
+                pos,
+                rot,
+                textures,
+            ));
         }
         WallKind::Window => {
             //  Window. Open at vertical center.
@@ -558,11 +1986,46 @@ fn draw_wall_section(
                 rot,
                 &textures.brick,
             ));
+            //  Glass, if any.
+            objects.extend(draw_glass_pane(
+                renderer,
+                Vec2::new(wall_width, opening_height),
+                Vec3::new(
+                    (column_thickness + wall_width) / 2.0,
+                    bottom_height + opening_height / 2.0,
+                    0.0,
+                ),
+                pos,
+                rot,
+                textures,
+            ));
         }
     }
     objects // return handles which keep objects alive
 }
 
+/// Draw a glass pane filling an opening, in the middle of the wall. None if the style has no glass.
+fn draw_glass_pane(
+    renderer: &Arc<Renderer>,
+    size: Vec2,   // width and height of opening
+    center: Vec3, // center of opening, relative to wall section
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Option<SceneObject> {
+    const GLASS_THICKNESS: f32 = 0.02;
+    textures.style.glass.map(|_| {
+        solids::create_simple_block(
+            renderer,
+            Vec3::new(size.x, size.y, GLASS_THICKNESS),
+            center,
+            pos,
+            rot,
+            &textures.glass,
+        )
+    })
+}
+
 /// Draw a floor section
 //  Pos is the same as for a story, the lower left hand corner.
 //  Floor texture on top, ceiling texture on bottom.
@@ -573,7 +2036,7 @@ fn draw_floor_and_ceiling(
     pos: Vec3,
     rot: Quat,
     textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+) -> Vec<SceneObject> {
     let thickness = size[1]; // thickness of floor
     let center = size * 0.5; // center of block relative to pos
     vec![
@@ -607,9 +2070,9 @@ fn draw_roof(
     pos: Vec3,
     rot: Quat,
     textures: &CityTextures,
-) -> Vec<ObjectHandle> {
+) -> Vec<SceneObject> {
     let center = size * 0.5 + Vec3::new(0.0, height, 0.0);
-    vec![
+    let mut objects = vec![
         solids::create_simple_block(
             // roof
             renderer,
@@ -655,5 +2118,217 @@ fn draw_roof(
             rot,
             &textures.stone,
         ),
+    ];
+    //  Round things on the roof, if the style has round shapes.
+    let tessellation = textures.style.tessellation;
+    if tessellation > 0 {
+        let roof_top = center + Vec3::new(0.0, thickness * 0.25, 0.0);
+        objects.extend(draw_dome(renderer, tessellation, size, roof_top, pos, rot, textures));
+        objects.extend(draw_roof_hut(renderer, size, roof_top, pos, rot, textures));
+        let half_extents = Vec3::new(size[0] + 2.0 * thickness, 0.0, size[2] + 2.0 * thickness) * 0.5;
+        let parapet_top = center + Vec3::new(0.0, thickness * 0.5, 0.0);
+        objects.extend(draw_railing(
+            renderer,
+            tessellation,
+            (parapet_top, half_extents),
+            pos,
+            rot,
+            textures,
+        ));
+    }
+    objects
+}
+
+/// Draw a dome on a drum, centered on the roof, with a molding and a spire.
+//  The lower half of the sphere is hidden inside the drum.
+fn draw_dome(
+    renderer: &Arc<Renderer>,
+    tessellation: u32,
+    roof_size: Vec3,
+    roof_top: Vec3, // center of roof surface, relative to pos
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    const DRUM_HEIGHT: f32 = 0.8;
+    const SPIRE_HEIGHT: f32 = 1.0;
+    let diameter = roof_size[0].min(roof_size[2]) * 0.5;
+    vec![
+        solids::create_prim(
+            // drum
+            renderer,
+            &Prim::new(PrimShape::Cylinder, tessellation),
+            Vec3::new(diameter, DRUM_HEIGHT, diameter),
+            roof_top + Vec3::new(0.0, DRUM_HEIGHT * 0.5, 0.0),
+            pos,
+            rot,
+            &textures.brick,
+        ),
+        solids::create_prim(
+            // dome
+            renderer,
+            &Prim::new(PrimShape::Sphere, tessellation),
+            Vec3::splat(diameter),
+            roof_top + Vec3::new(0.0, DRUM_HEIGHT, 0.0),
+            pos,
+            rot,
+            &textures.stone,
+        ),
+        solids::create_prim(
+            // square collar around the foot of the drum
+            renderer,
+            &Prim::new(PrimShape::Tube, tessellation),
+            Vec3::new(diameter + 0.3, 0.2, diameter + 0.3),
+            roof_top + Vec3::new(0.0, 0.1, 0.0),
+            pos,
+            rot,
+            &textures.stone,
+        ),
+        solids::create_prim(
+            // molding where dome meets drum
+            renderer,
+            &Prim::new(PrimShape::Torus, tessellation),
+            Vec3::new(diameter + 0.2, 0.1, diameter + 0.2),
+            roof_top + Vec3::new(0.0, DRUM_HEIGHT, 0.0),
+            pos,
+            rot,
+            &textures.stone,
+        ),
+        solids::create_prim(
+            // spire
+            renderer,
+            &Prim::cone(tessellation),
+            Vec3::new(0.2, SPIRE_HEIGHT, 0.2),
+            roof_top + Vec3::new(0.0, DRUM_HEIGHT + diameter * 0.5 + SPIRE_HEIGHT * 0.4, 0.0),
+            pos,
+            rot,
+            &textures.stone,
+        ),
+    ]
+}
+
+/// Draw a stair housing at one end of the roof, with a gable roof and an awning over the door.
+//  The housing is a hollow box, so the gable and awning are prisms lying on their sides.
+fn draw_roof_hut(
+    renderer: &Arc<Renderer>,
+    roof_size: Vec3,
+    roof_top: Vec3, // center of roof surface, relative to pos
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    const HUT_SIZE: Vec3 = Vec3::new(1.2, 2.2, 1.6);
+    const GABLE_HEIGHT: f32 = 0.6;
+    const OVERHANG: f32 = 0.1;
+    const AWNING_SIZE: Vec3 = Vec3::new(0.5, 0.3, 1.0); // out from wall, high, wide
+    let base = roof_top + Vec3::new(roof_size[0] * 0.5 - HUT_SIZE.x * 0.5 - 0.3, 0.0, 0.0);
+    //  Extruded shapes run along Y. Lying down, they run along Z, and their profile's Z is up.
+    let lying = rot * Quat::from_rotation_x(-PI * 0.5);
+    let awning_pos = base + Vec3::new(-(HUT_SIZE.x + AWNING_SIZE.x) * 0.5, HUT_SIZE.y * 0.8, 0.0);
+    vec![
+        solids::create_prim(
+            // walls
+            renderer,
+            &Prim {
+                hollow: 0.85,
+                ..Prim::new(PrimShape::Box, 4)
+            },
+            HUT_SIZE,
+            base + Vec3::new(0.0, HUT_SIZE.y * 0.5, 0.0),
+            pos,
+            rot,
+            &textures.brick,
+        ),
+        solids::create_prim(
+            // gable roof, ridge front to back
+            renderer,
+            &Prim::new(PrimShape::Prism, 3),
+            Vec3::new(
+                HUT_SIZE.x + 2.0 * OVERHANG,
+                HUT_SIZE.z + 2.0 * OVERHANG,
+                GABLE_HEIGHT,
+            ),
+            Vec3::ZERO,
+            pos + rot * (base + Vec3::new(0.0, HUT_SIZE.y + GABLE_HEIGHT * 0.5, 0.0)),
+            lying,
+            &textures.roof,
+        ),
+        solids::create_prim(
+            // awning over the door, which faces the dome, sloping away from the wall
+            renderer,
+            &Prim::new(PrimShape::Wedge, 3),
+            Vec3::new(AWNING_SIZE.x, AWNING_SIZE.z, AWNING_SIZE.y),
+            Vec3::ZERO,
+            pos + rot * awning_pos,
+            rot * Quat::from_rotation_y(PI) * Quat::from_rotation_x(-PI * 0.5),
+            &textures.roof,
+        ),
     ]
 }
+
+/// Draw a railing of posts and top rails around a rectangle.
+//  Each post and rail is its own object.
+fn draw_railing(
+    renderer: &Arc<Renderer>,
+    tessellation: u32,
+    (base, half_extents): (Vec3, Vec3), // center of base of railing, and half size, relative to pos
+    pos: Vec3,
+    rot: Quat,
+    textures: &CityTextures,
+) -> Vec<SceneObject> {
+    const RAIL_HEIGHT: f32 = 0.9;
+    const POST_DIAMETER: f32 = 0.06;
+    const RAIL_DIAMETER: f32 = 0.05;
+    const POST_SPACING: f32 = 1.0; // at most
+    let post = Prim::new(PrimShape::Cylinder, (tessellation / 2).max(3)); // posts are thin, need fewer sides
+    let mut objects = Vec::new();
+    //  Posts, along each side, corners included once.
+    let posts_x = (2.0 * half_extents.x / POST_SPACING).ceil().max(1.0) as usize;
+    let posts_z = (2.0 * half_extents.z / POST_SPACING).ceil().max(1.0) as usize;
+    let mut post_positions = Vec::new();
+    for side in [-1.0, 1.0] {
+        for n in 0..=posts_x {
+            let x = -half_extents.x + 2.0 * half_extents.x * n as f32 / posts_x as f32;
+            post_positions.push(Vec3::new(x, 0.0, side * half_extents.z));
+        }
+        for n in 1..posts_z {
+            let z = -half_extents.z + 2.0 * half_extents.z * n as f32 / posts_z as f32;
+            post_positions.push(Vec3::new(side * half_extents.x, 0.0, z));
+        }
+    }
+    for post_pos in post_positions {
+        objects.push(solids::create_prim(
+            renderer,
+            &post,
+            Vec3::new(POST_DIAMETER, RAIL_HEIGHT, POST_DIAMETER),
+            base + post_pos + Vec3::new(0.0, RAIL_HEIGHT * 0.5, 0.0),
+            pos,
+            rot,
+            &textures.stone,
+        ));
+    }
+    //  Top rails. Cylinders lie along Y, so turn them on their sides.
+    let rail = Prim::new(PrimShape::Cylinder, tessellation);
+    let rail_top = base + Vec3::new(0.0, RAIL_HEIGHT, 0.0);
+    for side in [-1.0, 1.0] {
+        objects.push(solids::create_prim(
+            renderer,
+            &rail,
+            Vec3::new(RAIL_DIAMETER, 2.0 * half_extents.x, RAIL_DIAMETER),
+            Vec3::ZERO,
+            pos + rot * (rail_top + Vec3::new(0.0, 0.0, side * half_extents.z)),
+            rot * Quat::from_rotation_z(PI * 0.5),
+            &textures.stone,
+        ));
+        objects.push(solids::create_prim(
+            renderer,
+            &rail,
+            Vec3::new(RAIL_DIAMETER, 2.0 * half_extents.z, RAIL_DIAMETER),
+            Vec3::ZERO,
+            pos + rot * (rail_top + Vec3::new(side * half_extents.x, 0.0, 0.0)),
+            rot * Quat::from_rotation_x(PI * 0.5),
+            &textures.stone,
+        ));
+    }
+    objects
+}
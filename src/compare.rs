@@ -0,0 +1,300 @@
+//  compare.rs -- compare two saved benchmark runs.
+//
+//  Part of render-bench.
+//
+//  Usage: render-bench compare <baseline results> <new results> [--threshold <percent>]
+//
+//  Prints per-metric deltas for each group of builder phases, with a
+//  significance check, and exits non-zero if the 99th percentile frame
+//  time while the scene is being changed got worse by more than the threshold.
+//
+use super::results::{BenchResults, FrameSummary, PhaseGroup};
+use anyhow::{anyhow, Error};
+use pico_args::Arguments;
+
+const HELP: &str = "\
+render-bench compare
+
+Compare two results files saved with --results.
+
+usage: render-bench compare <baseline> <new> --options
+
+Options:
+  --help                 This menu.
+  --threshold <percent>  Regression if p99 frame time while mutating grows by more than this. Default 10.
+  --alpha <p>            Significance level for the Mann-Whitney test. Default 0.01.
+
+Exit status is 0 if no regression, 1 if regression, 2 on error.
+";
+
+/// A metric from a summary, for the comparison table.
+type Metric = fn(&FrameSummary) -> f64;
+
+/// Result of comparing the two runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Improved,
+    NoChange,
+    Regressed,
+}
+
+/// The compare subcommand. Does not return.
+pub fn compare() {
+    let mut args = Arguments::from_vec(std::env::args_os().skip(2).collect()); // skip "compare"
+    if args.contains(["-h", "--help"]) {
+        eprintln!("{}", HELP);
+        std::process::exit(2);
+    }
+    let threshold: f64 = option_arg(args.opt_value_from_str("--threshold")).unwrap_or(10.0);
+    let alpha: f64 = option_arg(args.opt_value_from_str("--alpha")).unwrap_or(0.01);
+    let files: Vec<String> = args
+        .finish()
+        .iter()
+        .map(|s| s.to_string_lossy().to_string())
+        .collect();
+    if files.len() != 2 {
+        eprintln!("Need exactly two results files.\n\n{}", HELP);
+        std::process::exit(2);
+    }
+    match compare_files(&files[0], &files[1], threshold, alpha) {
+        Ok(Verdict::Regressed) => std::process::exit(1),
+        Ok(_) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Get option, or exit on a bad one.
+fn option_arg<T>(result: Result<Option<T>, pico_args::Error>) -> Option<T> {
+    match result {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, HELP);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Compare two results files, print the comparison, and return a verdict.
+pub fn compare_files(
+    baseline_file: &str,
+    new_file: &str,
+    threshold: f64,
+    alpha: f64,
+) -> Result<Verdict, Error> {
+    let baseline = BenchResults::load(baseline_file)?;
+    let new = BenchResults::load(new_file)?;
+    println!("Baseline: {} ({} frames)", baseline_file, baseline.frames.len());
+    for comment in &baseline.comments {
+        println!("  {}", comment);
+    }
+    println!("New:      {} ({} frames)", new_file, new.frames.len());
+    for comment in &new.comments {
+        println!("  {}", comment);
+    }
+    println!();
+    //  The verdict needs frames while mutating from both runs, or it measures nothing.
+    for (file, results) in [(baseline_file, &baseline), (new_file, &new)] {
+        if results.summary(PhaseGroup::Mutating).count == 0 {
+            return Err(anyhow!(
+                "No frames while mutating in {}, so nothing to compare",
+                file
+            ));
+        }
+    }
+    println!(
+        "{:<9} {:<7} {:>11} {:>11} {:>9}",
+        "group", "metric", "baseline", "new", "delta"
+    );
+    let mut verdict = Verdict::NoChange;
+    for group in PhaseGroup::ALL {
+        let b = baseline.summary(group);
+        let n = new.summary(group);
+        if b.count == 0 || n.count == 0 {
+            println!("{:<9} (no frames in one or both runs)", group.name());
+            continue;
+        }
+        let metrics: [(&str, Metric); 5] = [
+            ("mean", |s| s.mean),
+            ("p50", |s| s.p50),
+            ("p95", |s| s.p95),
+            ("p99", |s| s.p99),
+            ("max", |s| s.max),
+        ];
+        println!(
+            "{:<9} {:<7} {:>11} {:>11}",
+            group.name(),
+            "frames",
+            b.count,
+            n.count
+        );
+        for (name, metric) in metrics {
+            println!(
+                "{:<9} {:<7} {:>9.2}ms {:>9.2}ms {:>+8.1}%",
+                group.name(),
+                name,
+                metric(&b),
+                metric(&n),
+                percent_change(metric(&b), metric(&n))
+            );
+        }
+        let (_, p) = mann_whitney(&baseline.frame_times(group), &new.frame_times(group));
+        let significant = p < alpha;
+        println!(
+            "{:<9} Mann-Whitney p = {:.4} ({})",
+            group.name(),
+            p,
+            if significant { "significant" } else { "not significant" }
+        );
+        //  The verdict is based on p99 frame time during mutation.
+        if group == PhaseGroup::Mutating {
+            let change = percent_change(b.p99, n.p99);
+            verdict = if significant && change > threshold {
+                Verdict::Regressed
+            } else if significant && change < -threshold {
+                Verdict::Improved
+            } else {
+                Verdict::NoChange
+            };
+        }
+    }
+    println!();
+    match verdict {
+        Verdict::Regressed => println!(
+            "REGRESSION: p99 frame time while mutating got worse by more than {}%.",
+            threshold
+        ),
+        Verdict::Improved => println!(
+            "IMPROVED: p99 frame time while mutating got better by more than {}%.",
+            threshold
+        ),
+        Verdict::NoChange => println!(
+            "No significant change beyond {}% in p99 frame time while mutating.",
+            threshold
+        ),
+    }
+    Ok(verdict)
+}
+
+/// Percent change from a to b.
+fn percent_change(a: f64, b: f64) -> f64 {
+    if a == 0.0 {
+        0.0
+    } else {
+        (b - a) / a * 100.0
+    }
+}
+
+/// U statistic of a, and two-sided p-value, of the Mann-Whitney U test, normal approximation.
+//  Frame times are far from normally distributed, so we use a rank test.
+//  Frame times are in whole microseconds, so there are many ties, and the
+//  variance is corrected for them.
+fn mann_whitney(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let n1 = a.len() as f64;
+    let n2 = b.len() as f64;
+    if a.is_empty() || b.is_empty() {
+        return (0.0, 1.0);
+    }
+    //  Rank all values together, averaging ranks of ties.
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|v| (*v, true))
+        .chain(b.iter().map(|v| (*v, false)))
+        .collect();
+    all.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0; // sum of t^3 - t over groups of t tied values
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j < all.len() && all[j].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 * 0.5; // average of ranks i+1 ..= j
+        rank_sum_a += rank * all[i..j].iter().filter(|item| item.1).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+    let u = rank_sum_a - n1 * (n1 + 1.0) * 0.5;
+    let mean = n1 * n2 * 0.5;
+    let n = n1 + n2;
+    let sigma = (n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)))).sqrt();
+    if sigma.is_nan() || sigma == 0.0 {
+        return (u, 1.0); // all tied
+    }
+    let z = ((u - mean) / sigma).abs();
+    (u, 2.0 * (1.0 - normal_cdf(z)))
+}
+
+/// Standard normal cumulative distribution function.
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2))
+}
+
+/// Error function, Abramowitz and Stegun 7.1.26. Good to about 1e-7.
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let y = 1.0
+        - (((((1.061405429 * t - 1.453152027) * t) + 1.421413741) * t - 0.284496736) * t
+            + 0.254829592)
+            * t
+            * (-x * x).exp();
+    sign * y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Close enough, given the error function approximation.
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} is not near {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn separated_samples() {
+        let (u, p) = mann_whitney(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]);
+        assert_near(u, 0.0);
+        assert_near(p, 0.049535);
+        //  Symmetric.
+        let (u, p) = mann_whitney(&[4.0, 5.0, 6.0], &[1.0, 2.0, 3.0]);
+        assert_near(u, 9.0);
+        assert_near(p, 0.049535);
+    }
+
+    #[test]
+    fn overlapping_samples() {
+        let (u, p) = mann_whitney(&[1.1, 2.2, 3.3, 4.4, 5.5], &[3.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_near(u, 3.0);
+        assert_near(p, 0.047202);
+    }
+
+    #[test]
+    fn tied_samples() {
+        let (u, p) = mann_whitney(&[1.0, 2.0, 2.0, 3.0], &[2.0, 3.0, 3.0, 4.0]);
+        assert_near(u, 3.0);
+        assert_near(p, 0.129155);
+    }
+
+    #[test]
+    fn all_tied() {
+        let (u, p) = mann_whitney(&[5.0, 5.0, 5.0], &[5.0, 5.0]);
+        assert_near(u, 3.0);
+        assert_near(p, 1.0);
+    }
+
+    #[test]
+    fn empty_sample() {
+        assert_near(mann_whitney(&[], &[1.0, 2.0]).1, 1.0);
+    }
+}
@@ -0,0 +1,104 @@
+//  daylight.rs -- day and night cycle.
+//
+//  Part of render-bench.
+//
+//  The sun rises in the east, crosses the southern sky, and sets in the
+//  west, over a day of configurable length. Its color, intensity, the
+//  ambient level and the sky color follow it. At night the moon, opposite
+//  the sun and much dimmer, is the directional light, so there is always
+//  one light moving and casting shadows, and the shadow maps never settle.
+//
+//  Rend3 can only show a skybox texture, not tint it, so the skybox is
+//  shown by day and replaced by a plain sky color from dusk to dawn.
+//
+use core::f32::consts::PI;
+use glam::{Vec3, Vec4};
+
+/// Time of day at startup, as a fraction of a day from midnight. Mid-morning.
+const START_TIME: f32 = 0.3;
+/// Latitude, which sets how high the sun gets at noon.
+const LATITUDE: f32 = 40.0 * PI / 180.0;
+/// Moonlight, as a fraction of sunlight.
+const MOON_INTENSITY: f32 = 0.08;
+/// Ambient light at night, as a fraction of the daytime level.
+const NIGHT_AMBIENT: f32 = 0.25;
+
+/// Sun and sky settings over a day.
+#[derive(Debug, Copy, Clone)]
+pub struct DayCycle {
+    day_length: f32, // seconds per day
+    intensity: f32,  // sun intensity at full daylight
+    ambient: f32,    // ambient level at full daylight
+}
+
+/// Sun and sky at one moment.
+#[derive(Debug, Copy, Clone)]
+pub struct Sky {
+    pub direction: Vec3,   // direction the directional light shines, from sun or moon
+    pub color: Vec3,       // of the directional light
+    pub intensity: f32,    // of the directional light
+    pub ambient: f32,      // ambient light level
+    pub clear_color: Vec4, // sky color, where there is no skybox
+    pub show_skybox: bool, // daytime, so the skybox image fits
+}
+
+impl DayCycle {
+    /// Usual new
+    pub fn new(day_length: f32, intensity: f32, ambient: f32) -> DayCycle {
+        DayCycle {
+            day_length: day_length.max(1.0),
+            intensity,
+            ambient,
+        }
+    }
+
+    /// Sun and sky this many seconds after startup.
+    pub fn sky(&self, secs: f32) -> Sky {
+        let time_of_day = (START_TIME + secs / self.day_length).fract();
+        //  Angle along the sun's path: 0 at sunrise, PI/2 at noon, PI at sunset.
+        let angle = 2.0 * PI * (time_of_day - 0.25);
+        let toward_sun = Vec3::new(
+            angle.cos(),
+            angle.sin() * LATITUDE.cos(),
+            angle.sin() * LATITUDE.sin(),
+        );
+        let elevation = toward_sun.y; // sine of elevation
+
+        //  How much of full daylight, 0 at night, easing in around sunrise.
+        let daylight = smoothstep(-0.05, 0.25, elevation);
+        //  Near the horizon, light is redder.
+        let low_sun = Vec3::new(1.0, 0.55, 0.3);
+        let sun_color = low_sun.lerp(Vec3::ONE, smoothstep(0.0, 0.4, elevation));
+        let moon_color = Vec3::new(0.6, 0.7, 1.0);
+        let (direction, color, intensity) = if elevation > 0.0 {
+            (-toward_sun, sun_color, self.intensity * daylight)
+        } else {
+            //  Moon, opposite the sun, fading in as the sun goes down.
+            let moonlight = smoothstep(0.0, 0.2, -elevation);
+            (
+                toward_sun,
+                moon_color,
+                self.intensity * MOON_INTENSITY * moonlight,
+            )
+        };
+        let day_sky = Vec3::new(0.45, 0.6, 0.85);
+        let dusk_sky = Vec3::new(0.8, 0.45, 0.3);
+        let night_sky = Vec3::new(0.01, 0.012, 0.03);
+        let dusk = 1.0 - (elevation.abs() / 0.2).min(1.0); // 1 at the horizon
+        let sky_color = night_sky.lerp(day_sky, daylight).lerp(dusk_sky, dusk * 0.6);
+        Sky {
+            direction,
+            color,
+            intensity,
+            ambient: self.ambient * (NIGHT_AMBIENT + (1.0 - NIGHT_AMBIENT) * daylight),
+            clear_color: sky_color.extend(1.0),
+            show_skybox: daylight > 0.5,
+        }
+    }
+}
+
+/// Smooth step from 0 at low to 1 at high.
+fn smoothstep(low: f32, high: f32, x: f32) -> f32 {
+    let t = ((x - low) / (high - low)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -0,0 +1,237 @@
+//  furniture.rs -- furnish the interior of a building story.
+//
+//  Part of render-bench.
+//
+//  Tables, chairs, shelves of books, and floor lamps, built from solids.
+//  The floor is divided into slots, and each slot gets a piece of
+//  furniture or a group of them, at the requested density. Everything
+//  is its own object, because interiors are where object counts explode
+//  in metaverse content.
+//
+use super::random::Rng;
+use super::solids::{self, Prim, PrimShape, SceneObject, TextureSet};
+use core::f32::consts::PI;
+use glam::{Quat, Vec2, Vec3};
+use rend3::Renderer;
+use std::sync::Arc;
+
+/// Size of one furniture slot, meters square.
+const SLOT_SIZE: f32 = 1.6;
+/// Keep furniture this far from the walls.
+const WALL_MARGIN: f32 = 0.3;
+
+/// Furnishes rooms.
+pub struct Furnisher<'a> {
+    pub renderer: &'a Arc<Renderer>, // where objects go
+    pub density: f32,                // fraction of slots with furniture, 0 to 1
+    pub tessellation: u32,           // sides of round parts
+    pub wood: &'a TextureSet,        // tables, shelves
+    pub trim: &'a TextureSet,        // chairs, lamps, books
+}
+
+impl Furnisher<'_> {
+    /// Furnish a room. Pos is the corner of the room at floor level, like a story.
+    pub fn furnish(&self, size: Vec2, pos: Vec3, rot: Quat, rng: &mut Rng) -> Vec<SceneObject> {
+        profiling::scope!("Furnish room");
+        let mut objects = Vec::new();
+        let usable = size - Vec2::splat(2.0 * WALL_MARGIN);
+        let slots_x = (usable.x / SLOT_SIZE).floor() as usize;
+        let slots_z = (usable.y / SLOT_SIZE).floor() as usize;
+        for i in 0..slots_x {
+            for j in 0..slots_z {
+                if !rng.chance(self.density) {
+                    continue;
+                }
+                let center = Vec3::new(
+                    WALL_MARGIN + (i as f32 + 0.5) * SLOT_SIZE,
+                    0.0,
+                    WALL_MARGIN + (j as f32 + 0.5) * SLOT_SIZE,
+                );
+                let choice = rng.f32();
+                if choice < 0.4 {
+                    objects.extend(self.table_with_chairs(center, pos, rot, rng));
+                } else if choice < 0.65 {
+                    objects.extend(self.shelf(center, pos, rot, rng));
+                } else if choice < 0.85 {
+                    objects.extend(self.lamp(center, pos, rot));
+                } else {
+                    let facing = rng.range(0.0, 2.0 * PI);
+                    objects.push(self.chair(center, facing, pos, rot));
+                }
+            }
+        }
+        objects
+    }
+
+    /// Round table on a pedestal, with two to four chairs around it.
+    fn table_with_chairs(
+        &self,
+        center: Vec3,
+        pos: Vec3,
+        rot: Quat,
+        rng: &mut Rng,
+    ) -> Vec<SceneObject> {
+        const TABLE_HEIGHT: f32 = 0.74;
+        let round = Prim::new(PrimShape::Cylinder, self.tessellation);
+        let mut objects = vec![
+            solids::create_prim(
+                // top
+                self.renderer,
+                &round,
+                Vec3::new(0.9, 0.04, 0.9),
+                center + Vec3::new(0.0, TABLE_HEIGHT - 0.02, 0.0),
+                pos,
+                rot,
+                self.wood,
+            ),
+            solids::create_prim(
+                // pedestal
+                self.renderer,
+                &round,
+                Vec3::new(0.08, TABLE_HEIGHT - 0.04, 0.08),
+                center + Vec3::new(0.0, (TABLE_HEIGHT - 0.04) * 0.5, 0.0),
+                pos,
+                rot,
+                self.wood,
+            ),
+        ];
+        let chairs = 2 + rng.below(3);
+        let start = rng.range(0.0, 2.0 * PI);
+        for n in 0..chairs {
+            let angle = start + 2.0 * PI * n as f32 / chairs as f32;
+            let offset = Vec3::new(angle.sin(), 0.0, angle.cos()) * 0.6;
+            objects.push(self.chair(center + offset, angle + PI, pos, rot)); // facing table
+        }
+        objects
+    }
+
+    /// Chair. Seat, back, and legs, merged into one mesh.
+    //  Facing is rotation about Y of the direction the chair faces, from +Z.
+    fn chair(&self, center: Vec3, facing: f32, pos: Vec3, rot: Quat) -> SceneObject {
+        const SEAT_HEIGHT: f32 = 0.45;
+        const SEAT: f32 = 0.42;
+        const LEG: f32 = 0.04;
+        let leg = |x: f32, z: f32| {
+            (
+                Vec3::new(LEG, SEAT_HEIGHT, LEG),
+                Vec3::new(x * (SEAT - LEG) * 0.5, SEAT_HEIGHT * 0.5, z * (SEAT - LEG) * 0.5),
+            )
+        };
+        let parts = [
+            (Vec3::new(SEAT, 0.04, SEAT), Vec3::new(0.0, SEAT_HEIGHT, 0.0)), // seat
+            (
+                Vec3::new(SEAT, 0.45, 0.04),
+                Vec3::new(0.0, SEAT_HEIGHT + 0.25, -(SEAT - 0.04) * 0.5),
+            ), // back
+            leg(-1.0, -1.0),
+            leg(-1.0, 1.0),
+            leg(1.0, -1.0),
+            leg(1.0, 1.0),
+        ];
+        solids::create_merged_block(
+            self.renderer,
+            &parts,
+            pos + rot * center,
+            rot * Quat::from_rotation_y(facing),
+            self.trim,
+        )
+    }
+
+    /// Bookshelf, with books on some of its shelves.
+    fn shelf(&self, center: Vec3, pos: Vec3, rot: Quat, rng: &mut Rng) -> Vec<SceneObject> {
+        const WIDTH: f32 = 1.2;
+        const DEPTH: f32 = 0.35;
+        const HEIGHT: f32 = 1.8;
+        const BOARD: f32 = 0.03;
+        const SHELVES: usize = 4;
+        let spacing = (HEIGHT - BOARD) / (SHELVES - 1) as f32;
+        let mut parts = vec![
+            (
+                Vec3::new(BOARD, HEIGHT, DEPTH),
+                Vec3::new(-(WIDTH - BOARD) * 0.5, HEIGHT * 0.5, 0.0),
+            ),
+            (
+                Vec3::new(BOARD, HEIGHT, DEPTH),
+                Vec3::new((WIDTH - BOARD) * 0.5, HEIGHT * 0.5, 0.0),
+            ),
+        ];
+        for n in 0..SHELVES {
+            parts.push((
+                Vec3::new(WIDTH - 2.0 * BOARD, BOARD, DEPTH),
+                Vec3::new(0.0, BOARD * 0.5 + spacing * n as f32, 0.0),
+            ));
+        }
+        let mut objects = vec![solids::create_merged_block(
+            self.renderer,
+            &parts,
+            pos + rot * center,
+            rot,
+            self.wood,
+        )];
+        //  Books, each its own object, standing on the lower shelves.
+        for n in 0..SHELVES - 1 {
+            let shelf_top = BOARD + spacing * n as f32;
+            let mut x = -WIDTH * 0.5 + BOARD + 0.02;
+            for _ in 0..rng.below(6) {
+                let thickness = rng.range(0.03, 0.08);
+                let height = rng.range(0.18, (spacing - BOARD).min(0.3));
+                if x + thickness > WIDTH * 0.5 - BOARD {
+                    break;
+                }
+                objects.push(solids::create_simple_block(
+                    self.renderer,
+                    Vec3::new(thickness, height, 0.22),
+                    center + Vec3::new(x + thickness * 0.5, shelf_top + height * 0.5, 0.0),
+                    pos,
+                    rot,
+                    self.trim,
+                ));
+                x += thickness + rng.range(0.0, 0.05);
+            }
+        }
+        objects
+    }
+
+    /// Floor lamp. Base, pole, and an open tapered shade.
+    fn lamp(&self, center: Vec3, pos: Vec3, rot: Quat) -> Vec<SceneObject> {
+        const POLE_HEIGHT: f32 = 1.5;
+        let round = Prim::new(PrimShape::Cylinder, self.tessellation);
+        let shade = Prim {
+            taper: 0.5,
+            hollow: 0.9,
+            ..Prim::new(PrimShape::Cylinder, self.tessellation)
+        };
+        vec![
+            solids::create_prim(
+                // base
+                self.renderer,
+                &round,
+                Vec3::new(0.3, 0.03, 0.3),
+                center + Vec3::new(0.0, 0.015, 0.0),
+                pos,
+                rot,
+                self.trim,
+            ),
+            solids::create_prim(
+                // pole
+                self.renderer,
+                &round,
+                Vec3::new(0.03, POLE_HEIGHT, 0.03),
+                center + Vec3::new(0.0, POLE_HEIGHT * 0.5, 0.0),
+                pos,
+                rot,
+                self.trim,
+            ),
+            solids::create_prim(
+                // shade
+                self.renderer,
+                &shade,
+                Vec3::new(0.45, 0.3, 0.45),
+                center + Vec3::new(0.0, POLE_HEIGHT, 0.0),
+                pos,
+                rot,
+                self.wood,
+            ),
+        ]
+    }
+}
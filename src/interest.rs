@@ -0,0 +1,286 @@
+//  interest.rs -- interest-priority queue for pending uploads.
+//
+//  Part of render-bench.
+//
+//  In streaming mode, cells which come into range are not uploaded
+//  in grid order. They go into a queue ordered by how much of the
+//  screen they would cover, which depends on distance and on whether
+//  they are in front of the camera. The queue is reprioritized as the
+//  camera moves, and cells which leave range before they are uploaded
+//  are cancelled.
+//
+//  The queue also keeps pop-in statistics: how long each cell waited,
+//  and that wait weighted by screen size, since a big building in front
+//  of the camera showing up late is much more visible than a small one
+//  behind it.
+//
+use glam::Vec3;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Where the camera is and which way it is looking.
+#[derive(Debug, Copy, Clone)]
+pub struct CameraView {
+    pub position: Vec3, // camera position
+    pub forward: Vec3,  // unit vector, direction of view
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        CameraView {
+            position: Vec3::ZERO,
+            forward: Vec3::NEG_Z,
+        }
+    }
+}
+
+impl CameraView {
+    /// Approximate screen-space size of a bounding sphere, as angular radius in radians.
+    //  Things behind the camera still count a little, because the camera can turn.
+    pub fn screen_size(&self, center: Vec3, radius: f32) -> f32 {
+        const BEHIND_WEIGHT: f32 = 0.25; // relative interest of things behind the camera
+        let offset = center - self.position;
+        let distance = offset.length().max(radius).max(0.01);
+        let size = (radius / distance).asin();
+        let in_front = offset.dot(self.forward) > -radius;
+        if in_front {
+            size
+        } else {
+            size * BEHIND_WEIGHT
+        }
+    }
+}
+
+/// Order in which pending uploads are done.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UploadOrder {
+    Row,      // grid order, as in the non-streaming city
+    Interest, // largest on screen first
+}
+
+impl UploadOrder {
+    /// Parse from command line
+    pub fn parse(s: &str) -> Result<UploadOrder, &'static str> {
+        match s {
+            "row" => Ok(UploadOrder::Row),
+            "interest" => Ok(UploadOrder::Interest),
+            _ => Err("Upload order must be row or interest"),
+        }
+    }
+}
+
+/// A cell, by grid position.
+pub type CellKey = (i32, i32);
+
+/// One entry in the heap.
+//  Ordered by priority, then by grid position, lowest first, so that
+//  equal priorities come out in row order.
+#[derive(Debug, Copy, Clone)]
+struct Pending {
+    priority: f32, // higher goes first
+    key: CellKey,  // which cell
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .total_cmp(&other.priority)
+            .then_with(|| other.key.cmp(&self.key))
+    }
+}
+
+/// Queued cell bookkeeping.
+#[derive(Debug, Copy, Clone)]
+struct Queued {
+    since: Instant, // when it came into range
+    interest: f32,  // screen size as of last reprioritization
+}
+
+/// Priority queue of cells waiting to be uploaded.
+pub struct UploadQueue {
+    order: UploadOrder,                // how to order
+    heap: BinaryHeap<Pending>,         // pending cells, by priority
+    queued: HashMap<CellKey, Queued>,  // pending cells, by key
+    uploaded: usize,                   // cells popped since last report
+    cancelled: usize,                  // cells which left range while pending, since last report
+    total_wait: Duration,              // sum of waits of popped cells
+    max_wait: Duration,                // longest wait of a popped cell
+    weighted_wait: f64,                // sum of wait times screen size, in seconds
+    total_interest: f64,               // sum of screen sizes of popped cells
+}
+
+impl UploadQueue {
+    /// Usual new
+    pub fn new(order: UploadOrder) -> UploadQueue {
+        UploadQueue {
+            order,
+            heap: BinaryHeap::new(),
+            queued: HashMap::new(),
+            uploaded: 0,
+            cancelled: 0,
+            total_wait: Duration::ZERO,
+            max_wait: Duration::ZERO,
+            weighted_wait: 0.0,
+            total_interest: 0.0,
+        }
+    }
+
+    /// Nothing pending?
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Bring the queue up to date with what is in range now.
+    //  Cells in wanted but not queued are added, queued cells not in wanted
+    //  are cancelled, and everything is reprioritized.
+    pub fn update(&mut self, wanted: &[CellKey], interest: impl Fn(CellKey) -> f32) {
+        let now = Instant::now();
+        let mut still_wanted = HashMap::with_capacity(wanted.len());
+        for key in wanted {
+            let since = self.queued.get(key).map_or(now, |queued| queued.since);
+            still_wanted.insert(
+                *key,
+                Queued {
+                    since,
+                    interest: interest(*key),
+                },
+            );
+        }
+        self.cancelled += self
+            .queued
+            .keys()
+            .filter(|key| !still_wanted.contains_key(key))
+            .count();
+        self.queued = still_wanted;
+        let order = self.order;
+        self.heap = self
+            .queued
+            .iter()
+            .map(|(key, queued)| Pending {
+                priority: match order {
+                    UploadOrder::Row => 0.0,
+                    UploadOrder::Interest => queued.interest,
+                },
+                key: *key,
+            })
+            .collect();
+    }
+
+    /// Next cell to upload, if any.
+    pub fn pop(&mut self) -> Option<CellKey> {
+        let pending = self.heap.pop()?;
+        let queued = self.queued.remove(&pending.key)?;
+        let wait = queued.since.elapsed();
+        self.uploaded += 1;
+        self.total_wait += wait;
+        self.max_wait = self.max_wait.max(wait);
+        self.weighted_wait += wait.as_secs_f64() * queued.interest as f64;
+        self.total_interest += queued.interest as f64;
+        Some(pending.key)
+    }
+
+    /// Print pop-in stats, then clear.
+    pub fn report(&mut self) {
+        if self.uploaded > 0 {
+            println!(
+                "Upload queue: {} uploaded, {} cancelled, {} pending. \
+                Wait average: {:0>5.2}s; \
+                Max: {:0>5.2}s; \
+                Weighted by screen size: {:0>5.2}s",
+                self.uploaded,
+                self.cancelled,
+                self.queued.len(),
+                self.total_wait.as_secs_f32() / self.uploaded as f32,
+                self.max_wait.as_secs_f32(),
+                if self.total_interest > 0.0 {
+                    self.weighted_wait / self.total_interest
+                } else {
+                    0.0
+                },
+            );
+        }
+        let order = self.order;
+        let heap = std::mem::take(&mut self.heap);
+        let queued = std::mem::take(&mut self.queued);
+        *self = UploadQueue {
+            heap,
+            queued,
+            ..UploadQueue::new(order)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pop everything, in order.
+    fn drain(queue: &mut UploadQueue) -> Vec<CellKey> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    /// Interest of a cell seen from a camera, as the builder computes it.
+    fn interest(view: CameraView) -> impl Fn(CellKey) -> f32 {
+        move |key: CellKey| view.screen_size(Vec3::new(key.0 as f32, 0.0, key.1 as f32), 0.5)
+    }
+
+    #[test]
+    fn interest_pops_largest_first() {
+        let mut queue = UploadQueue::new(UploadOrder::Interest);
+        let sizes: HashMap<CellKey, f32> = [((0, 0), 0.1), ((1, 0), 0.3), ((2, 0), 0.2)].into();
+        queue.update(&[(0, 0), (1, 0), (2, 0)], |key| sizes[&key]);
+        assert_eq!(drain(&mut queue), vec![(1, 0), (2, 0), (0, 0)]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn row_pops_in_key_order() {
+        let mut queue = UploadQueue::new(UploadOrder::Row);
+        queue.update(&[(1, 1), (0, 2), (1, 0), (0, 1)], |key| key.0 as f32);
+        assert_eq!(drain(&mut queue), vec![(0, 1), (0, 2), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn dropped_cells_are_cancelled() {
+        let mut queue = UploadQueue::new(UploadOrder::Interest);
+        queue.update(&[(0, 0), (1, 0), (2, 0)], |_| 1.0);
+        queue.update(&[(0, 0), (2, 0)], |_| 1.0);
+        assert_eq!(queue.cancelled, 1);
+        let popped = drain(&mut queue);
+        assert_eq!(popped.len(), 2);
+        assert!(!popped.contains(&(1, 0)));
+    }
+
+    #[test]
+    fn camera_move_reorders() {
+        let wanted = [(-10, 0), (10, 0)];
+        let mut queue = UploadQueue::new(UploadOrder::Interest);
+        let west = CameraView {
+            position: Vec3::new(-8.0, 0.0, 0.0),
+            forward: Vec3::NEG_X,
+        };
+        queue.update(&wanted, interest(west));
+        assert_eq!(queue.heap.peek().map(|pending| pending.key), Some((-10, 0)));
+        let east = CameraView {
+            position: Vec3::new(8.0, 0.0, 0.0),
+            forward: Vec3::X,
+        };
+        queue.update(&wanted, interest(east));
+        assert_eq!(drain(&mut queue), vec![(10, 0), (-10, 0)]);
+    }
+}
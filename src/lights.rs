@@ -0,0 +1,128 @@
+//  lights.rs -- street lamp and window lights.
+//
+//  Part of render-bench.
+//
+//  Night scenes have many small lights, and they come and go with the
+//  buildings they belong to. Lit windows and street lamps get a point
+//  light each, added with the building and dropped with it. Rend3 at
+//  this version has point lights but no spot lights, so street lamps,
+//  which would be spot lights, are point lights too.
+//
+//  The number of lights at any one time can be capped. All builder threads
+//  draw lights from one budget, and dropped lights go back into it, so
+//  once the cap is reached, newly added buildings get the lights of the
+//  ones deleted before them.
+//
+use super::stats::{self, Op};
+use glam::Vec3;
+use rend3::types::{PointLight, PointLightHandle};
+use rend3::Renderer;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// What a light is for, which sets its color and reach.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LightKind {
+    Window,     // room light, seen through a window
+    StreetLamp, // lamp post globe
+}
+
+impl LightKind {
+    /// Rend3 light, at this position.
+    fn point_light(&self, position: Vec3) -> PointLight {
+        match self {
+            LightKind::Window => PointLight {
+                position,
+                color: Vec3::new(1.0, 0.85, 0.6), // warm incandescent
+                radius: 6.0,
+                intensity: 15.0,
+            },
+            LightKind::StreetLamp => PointLight {
+                position,
+                color: Vec3::new(1.0, 0.75, 0.45), // sodium vapor
+                radius: 12.0,
+                intensity: 40.0,
+            },
+        }
+    }
+}
+
+/// How many more lights may be added. Shared by all builder threads.
+#[derive(Debug)]
+pub struct LightBudget {
+    remaining: AtomicUsize, // lights which may still be added
+}
+
+impl LightBudget {
+    /// Allow up to max lights at once. usize::MAX for no limit.
+    pub fn new(max: usize) -> LightBudget {
+        LightBudget {
+            remaining: AtomicUsize::new(max),
+        }
+    }
+
+    /// No more lights may be added, for now.
+    pub fn is_used_up(&self) -> bool {
+        self.remaining.load(Ordering::Relaxed) == 0
+    }
+
+    /// Take one light from the budget, if any are left.
+    fn take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Give one light back.
+    fn give_back(&self) {
+        //  Saturating, so an unlimited budget stays unlimited.
+        let _ = self
+            .remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_add(1));
+    }
+}
+
+/// A light in the scene. Dropping it drops the light and returns it to the budget.
+pub struct Light {
+    _handle: PointLightHandle, // keeps the light alive
+    budget: Arc<LightBudget>,  // where it goes back to
+}
+
+impl Drop for Light {
+    fn drop(&mut self) {
+        self.budget.give_back();
+    }
+}
+
+/// Add lights at these places, as many as the budget allows, in order.
+pub fn add_lights(
+    renderer: &Arc<Renderer>,
+    budget: &Arc<LightBudget>,
+    places: &[(Vec3, LightKind)],
+) -> Vec<Light> {
+    profiling::scope!("Add lights");
+    let mut lights = Vec::new();
+    for (position, kind) in places {
+        if !budget.take() {
+            break; // out of lights
+        }
+        let handle = stats::timed(Op::AddLight, || {
+            renderer.add_point_light(kind.point_light(*position))
+        });
+        lights.push(Light {
+            _handle: handle,
+            budget: Arc::clone(budget),
+        });
+    }
+    lights
+}
+
+/// Parse light limit from command line. A count, or "all" for no limit.
+pub fn parse_light_limit(s: &str) -> Result<usize, &'static str> {
+    match s {
+        "all" => Ok(usize::MAX),
+        _ => s
+            .parse::<usize>()
+            .map_err(|_| "Lights must be a count or 'all'"),
+    }
+}
@@ -1,7 +1,28 @@
+mod animation;
+mod camera;
+mod churn;
 mod citybuilder;
+mod compare;
+mod daylight;
+mod furniture;
+mod interest;
+mod lights;
 mod platform;
+mod random;
+mod resolution;
+mod results;
+mod scatter;
+mod skybox;
 mod solids;
+mod stats;
+mod sweep;
+mod terrain;
 mod viewer;
+mod views;
 fn main() {
-    viewer::viewer();
+    match std::env::args().nth(1).as_deref() {
+        Some("compare") => compare::compare(),
+        Some("sweep") => sweep::sweep(),
+        _ => viewer::viewer(),
+    }
 }
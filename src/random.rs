@@ -0,0 +1,61 @@
+//  random.rs -- small seeded random number generator.
+//
+//  Part of render-bench.
+//
+//  Generated content must be the same on every run, so benchmark runs
+//  can be compared. So everything random comes from here, seeded from
+//  the command line seed and where the thing is. Not for cryptography.
+//
+use glam::Vec3;
+
+/// SplitMix64. Fast, small, and good enough for scattering furniture.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// New, from seed.
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// New, from seed and a position, so the same place always gets the same content.
+    pub fn at_position(seed: u64, pos: Vec3) -> Rng {
+        let mut rng = Rng::new(seed);
+        for coord in [pos.x, pos.y, pos.z] {
+            rng.state ^= coord.to_bits() as u64;
+            rng.next_u64();
+        }
+        rng
+    }
+
+    /// Next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in 0..1
+    pub fn f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in low..high
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.f32()
+    }
+
+    /// Uniform in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n.max(1) as u64) as usize
+    }
+
+    /// True with probability p.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.f32() < p
+    }
+}
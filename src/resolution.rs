@@ -0,0 +1,89 @@
+//  resolution.rs -- window size and render scale.
+//
+//  Part of render-bench.
+//
+//  The window can open at a given size instead of maximized, and the
+//  scene can be rendered at a scale of the window's resolution. The
+//  surface stays at the window's resolution. The render graph's internal
+//  targets are made at the scaled resolution, and the final tonemapping
+//  pass, which samples the internal color target over the whole output
+//  rectangle, scales the image up or down to fit. The scale can change
+//  from one frame to the next, since the graph is built anew each frame.
+//  With dynamic resolution, the render scale follows the frame time: down
+//  when frames are slower than a target, back up when they are well under
+//  it. If frame times fall as the scale does, rendering is fill-bound. If
+//  they stay the same, the time is going somewhere else, such as submission
+//  or waiting on scene updates.
+//
+use glam::UVec2;
+
+/// Lowest render scale dynamic resolution will go to.
+const MIN_SCALE: f32 = 0.25;
+/// Highest render scale anything will go to.
+const MAX_SCALE: f32 = 2.0;
+/// Step down, as a factor, when frames are too slow.
+const STEP_DOWN: f32 = 0.9;
+/// Step up, as a factor, when frames are fast.
+const STEP_UP: f32 = 1.05;
+/// Frames faster than this fraction of the target are fast enough to step up.
+const HEADROOM: f32 = 0.8;
+
+/// Parse window size from command line, as WxH.
+pub fn parse_resolution(value: &str) -> Result<UVec2, &'static str> {
+    let split: Vec<_> = value.split(['x', 'X']).map(|v| v.trim()).collect();
+    if split.len() != 2 {
+        return Err("Resolution is width and height, as 1920x1080");
+    }
+    let width: u32 = split[0].parse().map_err(|_| "Cannot parse resolution width")?;
+    let height: u32 = split[1].parse().map_err(|_| "Cannot parse resolution height")?;
+    if width == 0 || height == 0 {
+        return Err("Resolution must not be zero");
+    }
+    Ok(UVec2::new(width, height))
+}
+
+/// Render scale, fixed or following the frame time.
+#[derive(Debug, Copy, Clone)]
+pub struct RenderScale {
+    scale: f32,             // now
+    max: f32,               // never above this
+    target_ms: Option<f32>, // frame time to hold, if dynamic
+}
+
+impl RenderScale {
+    /// Start at scale. With a target frame time, dynamic, never above the starting scale.
+    pub fn new(scale: f32, target_ms: Option<f32>) -> RenderScale {
+        let scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        RenderScale {
+            scale,
+            max: scale,
+            target_ms,
+        }
+    }
+
+    /// Scale now.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Resolution to render at, for an output of this size.
+    pub fn internal(&self, size: UVec2) -> UVec2 {
+        (size.as_vec2() * self.scale)
+            .round()
+            .as_uvec2()
+            .max(UVec2::ONE)
+    }
+
+    /// Adjust for the mean frame time over the last second.
+    pub fn adjust(&mut self, mean_ms: f32) {
+        let target = match self.target_ms {
+            Some(target) => target,
+            None => return,
+        };
+        if mean_ms > target {
+            self.scale = (self.scale * STEP_DOWN).max(MIN_SCALE);
+        } else if mean_ms < target * HEADROOM {
+            self.scale = (self.scale * STEP_UP).min(self.max);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//  results.rs -- saved benchmark results.
+//
+//  Part of render-bench.
+//
+//  A run can save the time of every frame, along with what the city
+//  builder was doing during that frame, so that runs on different
+//  Rend3 revisions, drivers, or settings can be compared later.
+//
+//  File format is plain text, one frame per line:
+//
+//      # comment
+//      <phase> <frame time in microseconds>
+//
+use anyhow::{anyhow, Context, Error};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Groups of builder phases, for summaries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PhaseGroup {
+    All,      // everything after startup
+    Mutating, // builder adding or deleting content
+    Idle,     // builder waiting
+}
+
+impl PhaseGroup {
+    pub const ALL: [PhaseGroup; 3] = [PhaseGroup::All, PhaseGroup::Mutating, PhaseGroup::Idle];
+
+    /// Name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhaseGroup::All => "all",
+            PhaseGroup::Mutating => "mutating",
+            PhaseGroup::Idle => "idle",
+        }
+    }
+
+    /// Is this builder phase, by name, in this group?
+    pub fn contains(&self, phase: &str) -> bool {
+        let mutating = matches!(
+            phase,
+            "adding_permanent" | "adding" | "deleting" | "lod_swap" | "terrain_edit" | "modifying"
+        );
+        let idle = phase == "waiting";
+        match self {
+            PhaseGroup::All => mutating || idle,
+            PhaseGroup::Mutating => mutating,
+            PhaseGroup::Idle => idle,
+        }
+    }
+}
+
+/// One frame.
+#[derive(Debug, Clone)]
+pub struct FrameRecord {
+    pub phase: String, // builder phase name
+    pub usecs: u64,    // frame time
+}
+
+/// The results of one run.
+#[derive(Debug, Clone, Default)]
+pub struct BenchResults {
+    pub comments: Vec<String>,    // description of the run
+    pub frames: Vec<FrameRecord>, // all the frames, in order
+}
+
+/// Summary statistics for a set of frames, in milliseconds.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FrameSummary {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl BenchResults {
+    /// Add one frame.
+    pub fn push(&mut self, phase: &str, usecs: u64) {
+        self.frames.push(FrameRecord {
+            phase: phase.to_string(),
+            usecs,
+        });
+    }
+
+    /// Write to file.
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        let file =
+            File::create(filename).with_context(|| format!("Results file {}", filename))?;
+        let mut out = BufWriter::new(file);
+        for comment in &self.comments {
+            writeln!(out, "# {}", comment)?;
+        }
+        for frame in &self.frames {
+            writeln!(out, "{} {}", frame.phase, frame.usecs)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read from file.
+    pub fn load(filename: &str) -> Result<BenchResults, Error> {
+        let file = File::open(filename).with_context(|| format!("Results file {}", filename))?;
+        let mut results = BenchResults::default();
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                results.comments.push(comment.trim().to_string());
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (phase, usecs) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(phase), Some(usecs), None) => (phase, usecs),
+                _ => return Err(anyhow!("{} line {}: expected \"phase usecs\"", filename, n + 1)),
+            };
+            let usecs = usecs
+                .parse()
+                .with_context(|| format!("{} line {}: bad frame time", filename, n + 1))?;
+            results.push(phase, usecs);
+        }
+        Ok(results)
+    }
+
+    /// Frame times, in milliseconds, for the frames in a phase group.
+    pub fn frame_times(&self, group: PhaseGroup) -> Vec<f64> {
+        self.frames
+            .iter()
+            .filter(|frame| group.contains(&frame.phase))
+            .map(|frame| frame.usecs as f64 / 1_000.0)
+            .collect()
+    }
+
+    /// Summary of frame times for a phase group.
+    pub fn summary(&self, group: PhaseGroup) -> FrameSummary {
+        let mut times = self.frame_times(group);
+        if times.is_empty() {
+            return FrameSummary::default();
+        }
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let percentile = |p: f64| times[((times.len() - 1) as f64 * p / 100.0).round() as usize];
+        FrameSummary {
+            count: times.len(),
+            mean: times.iter().sum::<f64>() / times.len() as f64,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: *times.last().unwrap(),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//  scatter.rs -- trees and street furniture.
+//
+//  Part of render-bench.
+//
+//  Places trees, lamp posts, benches and signs with Poisson-disk sampling,
+//  so they are spread out naturally but never closer than some minimum
+//  distance. Street furniture goes on sidewalks, trees go anywhere there
+//  is open ground. Each item is made of its own objects, not instanced,
+//  because small, numerous, frequently changing objects are what make
+//  real scenes slow.
+//
+use super::random::Rng;
+use super::solids::{self, Prim, PrimShape, SceneObject, TextureSet};
+use core::f32::consts::PI;
+use glam::{Quat, Vec2, Vec3};
+use rend3::Renderer;
+use std::sync::Arc;
+
+/// Distance between items at density 1.0, meters.
+const BASE_SPACING: f32 = 4.0;
+/// Closest items can be at any density, meters. Keeps the sampling grid a sane size.
+const MIN_SPACING: f32 = 1.0;
+
+/// What kind of ground a point is on.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Ground {
+    Sidewalk { height: f32, yaw: f32 }, // raised; yaw turns +Z toward the roadway
+    Yard { height: f32 },               // open ground, following the terrain
+}
+
+/// Places trees and street furniture.
+pub struct Scatterer<'a> {
+    pub renderer: &'a Arc<Renderer>, // where objects go
+    pub density: f32,                // 1.0 is one item per BASE_SPACING
+    pub tessellation: u32,           // sides of round parts
+    pub bark: &'a TextureSet,        // tree trunks
+    pub leaves: &'a TextureSet,      // tree crowns
+    pub metal: &'a TextureSet,       // posts and signs
+    pub trim: &'a TextureSet,        // lamp globes, benches
+}
+
+impl Scatterer<'_> {
+    /// Minimum distance between items, from density.
+    pub fn spacing(&self) -> f32 {
+        (BASE_SPACING / self.density.max(0.01).sqrt()).max(MIN_SPACING)
+    }
+
+    /// Scatter items over a rectangle, relative to corner.
+    /// Returns the objects, and the centers of lamp globes, for lights.
+    //  Items only go where ground() says there is suitable ground.
+    pub fn scatter(
+        &self,
+        (min, max): (Vec2, Vec2),
+        corner: Vec3,
+        ground: &dyn Fn(Vec2) -> Option<Ground>,
+        rng: &mut Rng,
+    ) -> (Vec<SceneObject>, Vec<Vec3>) {
+        profiling::scope!("Scatter");
+        let mut objects = Vec::new();
+        let mut lamps = Vec::new();
+        for point in poisson_disk(rng, min, max, self.spacing()) {
+            let base = |height: f32| corner + Vec3::new(point.x, height, point.y);
+            match ground(point) {
+                Some(Ground::Yard { height }) => objects.extend(self.tree(base(height), rng)),
+                Some(Ground::Sidewalk { height, yaw }) => {
+                    let choice = rng.f32();
+                    if choice < 0.35 {
+                        objects.extend(self.tree(base(height), rng));
+                    } else if choice < 0.6 {
+                        let (lamp_post, globe) = self.lamp_post(base(height));
+                        objects.extend(lamp_post);
+                        lamps.push(globe);
+                    } else if choice < 0.85 {
+                        objects.push(self.bench(base(height), yaw));
+                    } else {
+                        objects.extend(self.sign(base(height), yaw));
+                    }
+                }
+                None => {}
+            }
+        }
+        (objects, lamps)
+    }
+
+    /// Tree. Trunk, and a round or pointed crown.
+    fn tree(&self, base: Vec3, rng: &mut Rng) -> Vec<SceneObject> {
+        let trunk_height = rng.range(1.8, 3.0);
+        let crown_size = rng.range(1.8, 3.2);
+        let conifer = rng.chance(0.3);
+        let (crown, crown_height) = if conifer {
+            (Prim::cone(self.tessellation), crown_size * 1.6)
+        } else {
+            (Prim::new(PrimShape::Sphere, self.tessellation), crown_size)
+        };
+        vec![
+            solids::create_prim(
+                // trunk
+                self.renderer,
+                &Prim::new(PrimShape::Cylinder, self.tessellation),
+                Vec3::new(0.25, trunk_height, 0.25),
+                Vec3::new(0.0, trunk_height * 0.5, 0.0),
+                base,
+                Quat::IDENTITY,
+                self.bark,
+            ),
+            solids::create_prim(
+                // crown, overlapping top of trunk
+                self.renderer,
+                &crown,
+                Vec3::new(crown_size, crown_height, crown_size),
+                Vec3::new(0.0, trunk_height + crown_height * 0.4, 0.0),
+                base,
+                Quat::IDENTITY,
+                self.leaves,
+            ),
+        ]
+    }
+
+    /// Lamp post. Pole and globe. Also returns the center of the globe.
+    fn lamp_post(&self, base: Vec3) -> (Vec<SceneObject>, Vec3) {
+        const HEIGHT: f32 = 4.0;
+        const GLOBE: f32 = 0.4;
+        let globe_center = Vec3::new(0.0, HEIGHT + GLOBE * 0.4, 0.0);
+        let objects = vec![
+            solids::create_prim(
+                // pole
+                self.renderer,
+                &Prim::new(PrimShape::Cylinder, self.tessellation),
+                Vec3::new(0.12, HEIGHT, 0.12),
+                Vec3::new(0.0, HEIGHT * 0.5, 0.0),
+                base,
+                Quat::IDENTITY,
+                self.metal,
+            ),
+            solids::create_prim(
+                // globe
+                self.renderer,
+                &Prim::new(PrimShape::Sphere, self.tessellation),
+                Vec3::splat(GLOBE),
+                globe_center,
+                base,
+                Quat::IDENTITY,
+                self.trim,
+            ),
+        ];
+        (objects, base + globe_center)
+    }
+
+    /// Bench, facing the street. Seat, back and legs, merged.
+    fn bench(&self, base: Vec3, yaw: f32) -> SceneObject {
+        const LENGTH: f32 = 1.5;
+        const SEAT_HEIGHT: f32 = 0.45;
+        const DEPTH: f32 = 0.45;
+        let leg = |x: f32| {
+            (
+                Vec3::new(0.06, SEAT_HEIGHT, DEPTH),
+                Vec3::new(x * (LENGTH * 0.5 - 0.1), SEAT_HEIGHT * 0.5, 0.0),
+            )
+        };
+        let parts = [
+            (
+                Vec3::new(LENGTH, 0.05, DEPTH),
+                Vec3::new(0.0, SEAT_HEIGHT, 0.0),
+            ), // seat
+            (
+                Vec3::new(LENGTH, 0.4, 0.05),
+                Vec3::new(0.0, SEAT_HEIGHT + 0.25, -DEPTH * 0.5),
+            ), // back
+            leg(-1.0),
+            leg(1.0),
+        ];
+        solids::create_merged_block(
+            self.renderer,
+            &parts,
+            base,
+            Quat::from_rotation_y(yaw),
+            self.trim,
+        )
+    }
+
+    /// Sign on a post, facing along the street.
+    fn sign(&self, base: Vec3, yaw: f32) -> Vec<SceneObject> {
+        const HEIGHT: f32 = 2.4;
+        let rot = Quat::from_rotation_y(yaw + PI * 0.5);
+        vec![
+            solids::create_prim(
+                // post
+                self.renderer,
+                &Prim::new(PrimShape::Cylinder, self.tessellation),
+                Vec3::new(0.06, HEIGHT, 0.06),
+                Vec3::new(0.0, HEIGHT * 0.5, 0.0),
+                base,
+                rot,
+                self.metal,
+            ),
+            solids::create_simple_block(
+                // board
+                self.renderer,
+                Vec3::new(0.6, 0.45, 0.03),
+                Vec3::new(0.0, HEIGHT - 0.3, 0.05),
+                base,
+                rot,
+                self.metal,
+            ),
+        ]
+    }
+}
+
+/// Poisson-disk sampling, Bridson's algorithm.
+//  Points in the rectangle min..max, no two closer than radius.
+pub fn poisson_disk(rng: &mut Rng, min: Vec2, max: Vec2, radius: f32) -> Vec<Vec2> {
+    const TRIES: usize = 30; // candidates per active point
+    let size = max - min;
+    if size.x <= 0.0 || size.y <= 0.0 || radius <= 0.0 {
+        return Vec::new();
+    }
+    //  Background grid, cells small enough to hold at most one point.
+    let cell = radius / 2.0_f32.sqrt();
+    let cols = (size.x / cell).ceil() as usize;
+    let rows = (size.y / cell).ceil() as usize;
+    let mut grid: Vec<Option<usize>> = vec![None; cols * rows];
+    let grid_index = |p: Vec2| {
+        let col = (((p.x - min.x) / cell) as usize).min(cols - 1);
+        let row = (((p.y - min.y) / cell) as usize).min(rows - 1);
+        (col, row)
+    };
+    let mut points = Vec::new();
+    let mut active = Vec::new();
+    let first = min + Vec2::new(rng.f32() * size.x, rng.f32() * size.y);
+    let (col, row) = grid_index(first);
+    grid[row * cols + col] = Some(0);
+    points.push(first);
+    active.push(0);
+    while !active.is_empty() {
+        let slot = rng.below(active.len());
+        let center = points[active[slot]];
+        let mut found = false;
+        for _ in 0..TRIES {
+            let angle = rng.range(0.0, 2.0 * PI);
+            let distance = rng.range(radius, 2.0 * radius);
+            let candidate = center + Vec2::new(angle.cos(), angle.sin()) * distance;
+            if candidate.cmplt(min).any() || candidate.cmpge(max).any() {
+                continue; // outside rectangle
+            }
+            let (col, row) = grid_index(candidate);
+            let too_close = (row.saturating_sub(2)..(row + 3).min(rows)).any(|r| {
+                (col.saturating_sub(2)..(col + 3).min(cols)).any(|c| {
+                    grid[r * cols + c].is_some_and(|n| points[n].distance(candidate) < radius)
+                })
+            });
+            if !too_close {
+                grid[row * cols + col] = Some(points.len());
+                active.push(points.len());
+                points.push(candidate);
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            active.swap_remove(slot);
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poisson_disk_spacing() {
+        let (min, max) = (Vec2::new(-5.0, 2.0), Vec2::new(25.0, 12.0));
+        let radius = 1.5;
+        let points = poisson_disk(&mut Rng::new(7), min, max, radius);
+        assert!(points.len() > 20, "only {} points", points.len());
+        for (n, p) in points.iter().enumerate() {
+            assert!(p.cmpge(min).all() && p.cmplt(max).all(), "{} is outside", p);
+            for q in &points[n + 1..] {
+                assert!(p.distance(*q) >= radius, "{} and {} are too close", p, q);
+            }
+        }
+    }
+
+    #[test]
+    fn poisson_disk_repeatable() {
+        let sample = |seed| poisson_disk(&mut Rng::new(seed), Vec2::ZERO, Vec2::splat(20.0), 2.0);
+        assert_eq!(sample(3), sample(3));
+        assert_ne!(sample(3), sample(4));
+    }
+
+    #[test]
+    fn poisson_disk_empty() {
+        let mut rng = Rng::new(1);
+        assert!(poisson_disk(&mut rng, Vec2::ZERO, Vec2::new(0.0, 5.0), 1.0).is_empty());
+        assert!(poisson_disk(&mut rng, Vec2::ZERO, Vec2::ONE, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+//  skybox.rs -- the background sky.
+//
+//  Part of render-bench.
+//
+//  The skybox is a cube texture, from six image files in a directory, or
+//  generated in memory as a plain gradient from horizon to zenith, or left
+//  out, in which case the clear color shows.
+//
+//  The images are kept after the first upload, so the skybox can be
+//  uploaded again while running. Each upload makes a new cube texture,
+//  which replaces the old one, so the cost of cube texture uploads can be
+//  measured the same way as the other Rend3 calls.
+//
+use anyhow::{anyhow, Context, Error};
+use glam::{UVec2, Vec3};
+use rend3::types::{Texture, TextureCubeHandle, TextureFormat};
+use rend3::Renderer;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::stats::{self, Op};
+
+/// Cube faces, in the order Rend3 wants them. Files are named for these.
+const FACES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+/// Image file types tried for each face, in order.
+const EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+/// Size of each face of the procedural sky, in pixels.
+const PROCEDURAL_SIZE: u32 = 256;
+
+/// Where the skybox comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkyboxSource {
+    Files(String), // directory with one image per face
+    Procedural,    // gradient, made in memory
+    None,          // no skybox, just the clear color
+}
+
+impl SkyboxSource {
+    /// Parse from command line
+    pub fn parse(s: &str) -> Result<SkyboxSource, &'static str> {
+        match s {
+            "" => Err("Skybox must be a directory, 'none', or 'procedural'"),
+            "none" => Ok(SkyboxSource::None),
+            "procedural" => Ok(SkyboxSource::Procedural),
+            _ => Ok(SkyboxSource::Files(s.to_string())),
+        }
+    }
+}
+
+/// All six faces of a skybox, as one big RGBA-ordered image, ready to upload.
+pub struct CubeImage {
+    label: String,    // where it came from
+    dims: (u32, u32), // size of each face
+    data: Vec<u8>,    // all faces, in FACES order
+}
+
+impl CubeImage {
+    /// Load or generate the images. None if no skybox.
+    pub fn load(source: &SkyboxSource) -> Result<Option<CubeImage>, Error> {
+        Ok(match source {
+            SkyboxSource::Files(dir) => Some(load_skybox(dir)?),
+            SkyboxSource::Procedural => Some(procedural_sky(PROCEDURAL_SIZE)),
+            SkyboxSource::None => None,
+        })
+    }
+
+    /// Upload as a new cube texture.
+    pub fn upload(&self, renderer: &Arc<Renderer>) -> TextureCubeHandle {
+        profiling::scope!("Add skybox");
+        let texture = Texture {
+            format: TextureFormat::Rgba8UnormSrgb,
+            size: UVec2::new(self.dims.0, self.dims.1),
+            data: self.data.clone(),
+            label: Some(self.label.clone()),
+            mip_count: rend3::types::MipmapCount::ONE,
+            mip_source: rend3::types::MipmapSource::Uploaded,
+        };
+        stats::timed(Op::AddTextureCube, || renderer.add_texture_cube(texture))
+            .expect("Error adding texture cube")
+    }
+}
+
+/// Load all faces of a skybox image. Output bytes as one big RGBA-ordered image.
+fn load_skybox_images(prefix: &str, filenames: &[&str]) -> Result<((u32, u32), Vec<u8>), Error> {
+    println!("Loading skybox textures.");
+    use image::{EncodableLayout, GenericImageView};
+    let mut v = Vec::new(); // accum bytes
+    let mut dims: Option<(u32, u32)> = None; // size of objects
+    if filenames.len() != 6 {
+        return Err(anyhow!("Skybox image set must have exactly 6 images"));
+    }
+    for filename in filenames {
+        let full_pathname = format!("{}/{}", prefix, filename);
+        let img = image::open(&full_pathname)
+            .with_context(|| format!("Skybox file {}", full_pathname))?;
+        //  Cube faces must be square.
+        if img.width() != img.height() {
+            return Err(anyhow!(
+                "Skybox image {} is {:?}, but cube faces must be square",
+                full_pathname,
+                img.dimensions()
+            ));
+        }
+        //  Check that all images have the same dimensions
+        match dims {
+            Some(dims) => {
+                if img.dimensions() != dims {
+                    return Err(anyhow!(
+                        "Skybox image {} is {:?} but others are {:?}",
+                        filename,
+                        img.dimensions(),
+                        dims
+                    ));
+                }
+            }
+            None => {
+                dims = Some(img.dimensions());
+            }
+        }
+        v.extend_from_slice(img.to_rgba8().as_bytes()); // load image
+    }
+    Ok((dims.unwrap(), v))
+}
+
+/// Load the skybox from individual images, one per face, in a directory.
+//  Each face is named for its side, as "right.jpg", and may be JPEG or PNG.
+fn load_skybox(dir: &str) -> Result<CubeImage, Error> {
+    if !Path::new(dir).is_dir() {
+        return Err(anyhow!("Skybox directory {} not found", dir));
+    }
+    let mut filenames = Vec::new();
+    for face in FACES {
+        let filename = EXTENSIONS
+            .iter()
+            .map(|ext| format!("{}.{}", face, ext))
+            .find(|filename| Path::new(dir).join(filename).is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Skybox directory {} has no {} image. Faces must be named {}, as .jpg or .png",
+                    dir,
+                    face,
+                    FACES.join(", ")
+                )
+            })?;
+        filenames.push(filename);
+    }
+    let filenames: Vec<&str> = filenames.iter().map(String::as_str).collect();
+    let (dims, data) = load_skybox_images(dir, &filenames)?; // Combine into one big texture
+    Ok(CubeImage {
+        label: format!("skybox {}", dir),
+        dims,
+        data,
+    })
+}
+
+/// Make a sky which is a gradient, pale at the horizon and deeper blue overhead, with dim ground below.
+fn procedural_sky(size: u32) -> CubeImage {
+    let zenith = Vec3::new(70.0, 120.0, 200.0);
+    let horizon = Vec3::new(190.0, 210.0, 230.0);
+    let ground = Vec3::new(90.0, 85.0, 80.0);
+    let mut data = Vec::with_capacity((size * size * 4 * 6) as usize);
+    for face in 0..FACES.len() {
+        for y in 0..size {
+            for x in 0..size {
+                //  Direction through this pixel, per the usual cube map layout.
+                let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let dir = match face {
+                    0 => Vec3::new(1.0, -v, -u),
+                    1 => Vec3::new(-1.0, -v, u),
+                    2 => Vec3::new(u, 1.0, v),
+                    3 => Vec3::new(u, -1.0, -v),
+                    4 => Vec3::new(u, -v, 1.0),
+                    _ => Vec3::new(-u, -v, -1.0),
+                }
+                .normalize();
+                let color = if dir.y >= 0.0 {
+                    horizon.lerp(zenith, dir.y.sqrt())
+                } else {
+                    horizon.lerp(ground, (-dir.y * 4.0).min(1.0))
+                };
+                data.extend_from_slice(&[color.x as u8, color.y as u8, color.z as u8, 255]);
+            }
+        }
+    }
+    CubeImage {
+        label: "skybox procedural".to_string(),
+        dims: (size, size),
+        data,
+    }
+}
@@ -7,76 +7,578 @@
 //  Animats
 //  April, 2022.
 //
-use std::sync::{Arc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use anyhow::{Context, Error};
 use glam::{Mat3, Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
 use image::RgbaImage;
 use rend3::{
     types::{
-        MaterialHandle, Mesh, MeshBuilder, Object, ObjectHandle, Texture, TextureFormat,
-        Texture2DHandle,
+        MaterialComponent, MaterialHandle, Mesh, MeshBuilder, MeshHandle, Object, ObjectHandle, Texture,
+        TextureFormat, Texture2DHandle,
     },
     Renderer,
 };
 
+use super::random::Rng;
+use super::stats::{self, Op};
+use core::f32::consts::{PI, TAU};
 use core::num::NonZeroU32;
-use rend3_routine::pbr::{AlbedoComponent, NormalTexture, PbrMaterial};
+use rend3_routine::pbr::{AlbedoComponent, AoMRTextures, NormalTexture, PbrMaterial, Transparency};
+
+/// What blocks share with each other.
+//  Textures are always shared. By default, meshes and materials are not,
+//  because we do it that way in the SL viewer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sharing {
+    None,      // each block gets its own mesh and material
+    Materials, // blocks with the same textures share a material
+    All,       // also, blocks with the same shape share a mesh
+}
+
+impl Sharing {
+    /// Parse from command line
+    pub fn parse(value: &str) -> Result<Sharing, &'static str> {
+        Ok(match value.to_lowercase().as_str() {
+            "none" => Sharing::None,
+            "materials" => Sharing::Materials,
+            "all" => Sharing::All,
+            _ => return Err("sharing must be none, materials, or all"),
+        })
+    }
+}
+
+/// Material parameters beyond textures.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Surface {
+    pub tint: Vec4,                 // multiplies the albedo texture
+    pub metallic: f32,              // 0 to 1
+    pub roughness: f32,             // 0 to 1
+    pub emissive: Vec3,             // glow, black for none
+    pub uv_transform: Mat3,         // texture placement
+    pub transparency: Transparency, // opaque, or how glass lets light through
+}
+
+impl Surface {
+    /// Untinted, a bit shiny, no glow, texture as mapped.
+    pub fn plain(transparency: Transparency) -> Surface {
+        Surface {
+            tint: Vec4::ONE,
+            metallic: 0.2,
+            roughness: 0.2,
+            emissive: Vec3::ZERO,
+            uv_transform: Mat3::IDENTITY,
+            transparency,
+        }
+    }
+}
+
+/// How much materials vary between objects with the same textures.
+//  Each texture set has a fixed number of variants, and each object gets
+//  one of them, by position. Variant 0 is the plain surface. With shared
+//  materials, there is one material per variant, so the number of
+//  variants sets how many distinct materials there are.
+#[derive(Debug, Copy, Clone)]
+pub struct Variety {
+    pub variants: usize, // distinct surfaces per texture set. 1 for all plain.
+    pub amount: f32,     // how far variants stray from plain, 0 to 1
+    pub seed: u64,       // for everything random
+}
+
+impl Variety {
+    /// Which variant an object here gets.
+    fn choose(&self, pos: Vec3) -> usize {
+        const VARIANT_SALT: u64 = 0x7a71_e7e5; // so choices do not follow furniture
+        if self.variants <= 1 {
+            0
+        } else {
+            Rng::at_position(self.seed ^ VARIANT_SALT, pos).below(self.variants)
+        }
+    }
+
+    /// The surface of one variant of a texture set. Same every run.
+    fn surface(&self, name: &str, variant: usize, transparency: Transparency) -> Surface {
+        let plain = Surface::plain(transparency);
+        if variant == 0 || self.amount <= 0.0 {
+            return plain;
+        }
+        let name_hash = name.bytes().fold(self.seed, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+        });
+        let mut rng = Rng::new(name_hash ^ variant as u64);
+        let amount = self.amount.min(1.0);
+        let mut shade = || 1.0 - amount * rng.range(0.0, 0.5);
+        let tint = Vec4::new(shade(), shade(), shade(), 1.0);
+        let metallic = plain.metallic + (rng.f32() - plain.metallic) * amount;
+        let roughness = plain.roughness + (rng.range(0.05, 1.0) - plain.roughness) * amount;
+        let emissive = if rng.chance(0.1 * amount) {
+            Vec3::new(rng.f32(), rng.f32(), rng.f32()) * 0.5 * amount
+        } else {
+            Vec3::ZERO
+        };
+        let uv_transform = Mat3::from_scale_angle_translation(
+            Vec2::splat(1.0 + amount * rng.range(-0.5, 1.0)),
+            amount * rng.range(-PI, PI),
+            Vec2::new(rng.f32(), rng.f32()) * amount,
+        );
+        Surface {
+            tint,
+            metallic,
+            roughness,
+            emissive,
+            uv_transform,
+            transparency,
+        }
+    }
+}
+
+/// Materials and meshes shared between blocks, per the sharing mode.
+pub struct AssetCache {
+    sharing: Sharing,
+    variety: Variety,                                           // how materials vary
+    materials: Mutex<HashMap<(String, usize), MaterialHandle>>, // by texture set name and variant
+    meshes: Mutex<HashMap<Vec<u32>, MeshHandle>>, // by bits of shape, scale, offset, texture scale
+}
+
+impl AssetCache {
+    /// Usual new
+    pub fn new(sharing: Sharing, variety: Variety) -> AssetCache {
+        AssetCache {
+            sharing,
+            variety,
+            materials: Mutex::new(HashMap::new()),
+            meshes: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Textures for one kind of surface, and the cache used when making blocks from them.
+pub struct TextureSet {
+    pub name: String,                  // texture set name, for sharing
+    pub albedo: Texture2DHandle,       // albedo texture
+    pub normal: Texture2DHandle,       // normal texture
+    pub aomr: Option<Texture2DHandle>, // ambient occlusion, roughness, metallic texture, if any
+    pub scale: f32,                    // texture scale
+    pub cache: Arc<AssetCache>,        // shared materials and meshes
+    pub transparency: Transparency,    // opaque, or how glass lets light through
+}
+
+impl TextureSet {
+    /// The textures a material made from this set uses.
+    fn material_textures(&self) -> MaterialTextures {
+        (self.albedo.clone(), self.normal.clone(), self.aomr.clone())
+    }
+}
+
+/// Albedo, normal, and optional ambient occlusion, roughness, metallic textures of a material.
+pub type MaterialTextures = (Texture2DHandle, Texture2DHandle, Option<Texture2DHandle>);
+
+/// Parse glass mode from command line. None for no glass.
+pub fn parse_glass(value: &str) -> Result<Option<Transparency>, &'static str> {
+    Ok(match value.to_lowercase().as_str() {
+        "none" => None,
+        "blend" => Some(Transparency::Blend),
+        "cutout" => Some(Transparency::Cutout { cutout: 0.5 }),
+        _ => return Err("glass must be none, blend, or cutout"),
+    })
+}
 
 /// Create a simple block.
-//  Each block gets its own material, because we do it that way in the SL viewer.
+//  Each block gets its own material, because we do it that way in the SL viewer,
+//  unless the texture set's cache says to share.
 //  No instancing here.
 pub fn create_simple_block(
     renderer: &Arc<Renderer>,
-    scale: Vec3,                                        // this rescales the actual mesh
-    offset: Vec3,                                       // this offsets the coords in the mesh
-    pos: Vec3,                                          // position in transform
-    rot: Quat,                                          // rotation
-    texture_info: &(Texture2DHandle, Texture2DHandle, f32), // (albedo, normal, scale)
-) -> ObjectHandle {
+    scale: Vec3,              // this rescales the actual mesh
+    offset: Vec3,             // this offsets the coords in the mesh
+    pos: Vec3,                // position in transform
+    rot: Quat,                // rotation
+    texture_set: &TextureSet, // albedo, normal, scale
+) -> SceneObject {
     profiling::scope!("Add block");
-    let (albedo_handle, normal_handle, texture_scale) = texture_info; // unpack tuple
-                                                                      ////println!("Add built-in object at {:?} size {:?}", pos, scale); // ***TEMP***
-    let material = create_simple_material(renderer, albedo_handle, normal_handle); // the texture
-    let mesh = create_mesh(scale, offset, *texture_scale);
+    ////println!("Add built-in object at {:?} size {:?}", pos, scale); // ***TEMP***
+    let (material, surface) = block_material(renderer, texture_set, pos + rot * offset); // the texture
+    let key = [
+        scale.x, scale.y, scale.z, offset.x, offset.y, offset.z, texture_set.scale,
+    ]
+    .map(f32::to_bits);
+    let mesh_handle = block_mesh(renderer, texture_set, &key, || {
+        create_mesh(scale, offset, texture_set.scale)
+    });
+    let transform = Mat4::from_scale_rotation_translation(Vec3::ONE, rot, pos);
+    let shape = Shape::Block(scale, offset);
+    add_block_object(
+        renderer,
+        (mesh_handle, material, surface),
+        transform,
+        texture_set,
+        shape,
+    )
+}
+
+/// Create a primitive solid, such as a cylinder or sphere.
+//  Works like create_simple_block. The prim is unit size before scaling.
+pub fn create_prim(
+    renderer: &Arc<Renderer>,
+    prim: &Prim,              // shape, cut, hollow, tessellation
+    scale: Vec3,              // this rescales the actual mesh
+    offset: Vec3,             // this offsets the coords in the mesh
+    pos: Vec3,                // position in transform
+    rot: Quat,                // rotation
+    texture_set: &TextureSet, // albedo, normal, scale
+) -> SceneObject {
+    profiling::scope!("Add prim");
+    let (material, surface) = block_material(renderer, texture_set, pos + rot * offset);
+    let key: Vec<u32> = prim
+        .key()
+        .into_iter()
+        .chain(
+            [
+                scale.x, scale.y, scale.z, offset.x, offset.y, offset.z, texture_set.scale,
+            ]
+            .map(f32::to_bits),
+        )
+        .collect();
+    let mesh_handle = block_mesh(renderer, texture_set, &key, || {
+        create_prim_mesh(prim, scale, offset, texture_set.scale)
+    });
+    let transform = Mat4::from_scale_rotation_translation(Vec3::ONE, rot, pos);
+    let shape = Shape::Prim(*prim, scale, offset);
+    add_block_object(
+        renderer,
+        (mesh_handle, material, surface),
+        transform,
+        texture_set,
+        shape,
+    )
+}
+
+/// Mesh for a block or prim, new or shared per the sharing mode.
+//  Key is everything which determines the mesh.
+fn block_mesh(
+    renderer: &Arc<Renderer>,
+    texture_set: &TextureSet,
+    key: &[u32],
+    create: impl FnOnce() -> Mesh,
+) -> MeshHandle {
+    let cache = &texture_set.cache;
+    let add_mesh = || {
+        profiling::scope!("Add mesh");
+        let mesh = create();
+        stats::timed(Op::AddMesh, || renderer.add_mesh(mesh)).expect("Error adding mesh")
+    };
+    if cache.sharing == Sharing::All {
+        cache
+            .meshes
+            .lock()
+            .unwrap()
+            .entry(key.to_vec())
+            .or_insert_with(add_mesh)
+            .clone()
+    } else {
+        add_mesh()
+    }
+}
+
+/// Create a block made of several boxes merged into one mesh.
+//  Parts are (scale, offset), as for create_simple_block.
+//  Merged meshes are never shared.
+pub fn create_merged_block(
+    renderer: &Arc<Renderer>,
+    parts: &[(Vec3, Vec3)], // (scale, offset) of each box
+    pos: Vec3,              // position in transform
+    rot: Quat,              // rotation
+    texture_set: &TextureSet,
+) -> SceneObject {
+    profiling::scope!("Add merged block");
+    let middle = parts.iter().map(|(_, offset)| *offset).sum::<Vec3>() / parts.len().max(1) as f32;
+    let (material, surface) = block_material(renderer, texture_set, pos + rot * middle);
+    let mesh = create_merged_mesh(parts, texture_set.scale);
     let mesh_handle = {
         profiling::scope!("Add mesh");
-        renderer.add_mesh(mesh).expect("Error adding mesh")
+        stats::timed(Op::AddMesh, || renderer.add_mesh(mesh)).expect("Error adding mesh")
     };
-    //  Add object to Rend3 system
-    profiling::scope!("Add object");
-    renderer.add_object(Object {
-        mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+    let transform = Mat4::from_scale_rotation_translation(Vec3::ONE, rot, pos);
+    let shape = Shape::Boxes(parts.to_vec());
+    add_block_object(
+        renderer,
+        (mesh_handle, material, surface),
+        transform,
+        texture_set,
+        shape,
+    )
+}
+
+/// Create a heightfield, such as a terrain tile.
+//  Square, size on a side, with corner at pos. Height is a function of
+//  world X and Z. Heightfields are never shared.
+pub fn create_heightfield(
+    renderer: &Arc<Renderer>,
+    (size, resolution): (f32, usize), // size on a side, and quads on a side
+    height: &dyn Fn(Vec2) -> f32,     // height at world X and Z
+    pos: Vec3,                        // position of corner
+    texture_set: &TextureSet,
+) -> SceneObject {
+    profiling::scope!("Add heightfield");
+    let (material, surface) = block_material(renderer, texture_set, pos);
+    let origin = Vec2::new(pos.x, pos.z);
+    let mesh = create_heightfield_mesh((size, resolution), height, origin, texture_set.scale);
+    let mesh_handle = {
+        profiling::scope!("Add mesh");
+        stats::timed(Op::AddMesh, || renderer.add_mesh(mesh)).expect("Error adding mesh")
+    };
+    let transform = Mat4::from_translation(pos);
+    add_block_object(
+        renderer,
+        (mesh_handle, material, surface),
+        transform,
+        texture_set,
+        Shape::Fixed,
+    )
+}
+
+/// Material for a block at pos, new or shared per the sharing mode, and its surface.
+//  The surface variant depends on position, per the cache's variety. The
+//  position is where the part is in the world, after its offset, so parts
+//  placed from one story or face position still vary.
+fn block_material(
+    renderer: &Arc<Renderer>,
+    texture_set: &TextureSet,
+    pos: Vec3,
+) -> (MaterialHandle, Surface) {
+    let cache = &texture_set.cache;
+    let variant = cache.variety.choose(pos);
+    let surface = cache
+        .variety
+        .surface(&texture_set.name, variant, texture_set.transparency);
+    let material = if cache.sharing == Sharing::None {
+        create_simple_material(renderer, &texture_set.material_textures(), &surface)
+    } else {
+        cache
+            .materials
+            .lock()
+            .unwrap()
+            .entry((texture_set.name.clone(), variant))
+            .or_insert_with(|| {
+                create_simple_material(renderer, &texture_set.material_textures(), &surface)
+            })
+            .clone()
+    };
+    (material, surface)
+}
+
+/// Add object to Rend3 system
+fn add_block_object(
+    renderer: &Arc<Renderer>,
+    (mesh_handle, material, surface): (MeshHandle, MaterialHandle, Surface),
+    transform: Mat4,
+    texture_set: &TextureSet,
+    shape: Shape,
+) -> SceneObject {
+    SceneObject {
+        object: add_object(renderer, mesh_handle, &material, transform),
         material,
-        transform: Mat4::from_scale_rotation_translation(Vec3::ONE, rot, pos),
+        shared: texture_set.cache.sharing != Sharing::None,
+        textures: texture_set.material_textures(),
+        texture_scale: texture_set.scale,
+        surface,
+        shape,
+        home: transform,
+        transform,
+    }
+}
+
+/// Add one object, timed.
+fn add_object(
+    renderer: &Arc<Renderer>,
+    mesh_handle: MeshHandle,
+    material: &MaterialHandle,
+    transform: Mat4,
+) -> ObjectHandle {
+    profiling::scope!("Add object");
+    stats::timed(Op::AddObject, || {
+        renderer.add_object(Object {
+            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+            material: material.clone(),
+            transform,
+        })
     })
 }
 
+/// How a mesh was made, so that it can be made again, or edited.
+#[derive(Debug, Clone)]
+enum Shape {
+    Block(Vec3, Vec3),        // scale, offset
+    Prim(Prim, Vec3, Vec3),   // prim, scale, offset
+    Boxes(Vec<(Vec3, Vec3)>), // merged boxes, (scale, offset) of each
+    Fixed,                    // cannot be made again, such as a heightfield
+}
+
+impl Shape {
+    /// Make the mesh, if possible.
+    fn mesh(&self, texture_scale: f32) -> Option<Mesh> {
+        match self {
+            Shape::Block(scale, offset) => Some(create_mesh(*scale, *offset, texture_scale)),
+            Shape::Prim(prim, scale, offset) => {
+                Some(create_prim_mesh(prim, *scale, *offset, texture_scale))
+            }
+            Shape::Boxes(parts) => Some(create_merged_mesh(parts, texture_scale)),
+            Shape::Fixed => None,
+        }
+    }
+
+    /// Resized, as a builder would edit a prim. Offsets scale too, so parts stay together.
+    fn resized(&self, factor: Vec3) -> Shape {
+        let resize = |(scale, offset): (Vec3, Vec3)| (scale * factor, offset * factor);
+        match self {
+            Shape::Block(scale, offset) => {
+                let (scale, offset) = resize((*scale, *offset));
+                Shape::Block(scale, offset)
+            }
+            Shape::Prim(prim, scale, offset) => {
+                let (scale, offset) = resize((*scale, *offset));
+                Shape::Prim(*prim, scale, offset)
+            }
+            Shape::Boxes(parts) => Shape::Boxes(parts.iter().copied().map(resize).collect()),
+            Shape::Fixed => Shape::Fixed,
+        }
+    }
+}
+
+/// An object in the scene, with what it takes to change it later.
+//  Dropping it drops the object.
+pub struct SceneObject {
+    object: ObjectHandle,       // keeps the object alive
+    material: MaterialHandle,   // its material, which may be shared
+    shared: bool,               // material is shared, so kept when the object is made again
+    textures: MaterialTextures, // for new materials
+    texture_scale: f32,         // for new meshes
+    surface: Surface,           // tint, shine, and so on, for new materials
+    shape: Shape,               // how the mesh was made
+    home: Mat4,                 // transform when created
+    transform: Mat4,            // transform now
+}
+
+impl SceneObject {
+    /// Move away from where it was created, or back if already moved, like a door.
+    pub fn swing(&mut self, renderer: &Arc<Renderer>, away: Mat4) {
+        let transform = if self.transform == self.home {
+            self.home * away
+        } else {
+            self.home
+        };
+        self.set_transform(renderer, transform);
+    }
+
+    /// Move to a new transform.
+    pub fn set_transform(&mut self, renderer: &Arc<Renderer>, transform: Mat4) {
+        stats::timed(Op::SetTransform, || {
+            renderer.set_object_transform(&self.object, transform)
+        });
+        self.transform = transform;
+    }
+
+    /// Change the material in place, to the same textures and surface with a tint.
+    //  If materials are shared, this changes every object sharing it.
+    pub fn tint(&mut self, renderer: &Arc<Renderer>, tint: Vec4) {
+        let surface = Surface {
+            tint: self.surface.tint * tint,
+            ..self.surface
+        };
+        let material = simple_pbr_material(&self.textures, &surface);
+        stats::timed(Op::UpdateMaterial, || {
+            renderer.update_material(&self.material, material)
+        });
+    }
+
+    /// Replace the mesh with a resized one. False if the mesh cannot be made again.
+    //  Rend3 objects cannot change meshes, so this adds a new object with
+    //  the new mesh and the same material, then drops the old object.
+    pub fn resize(&mut self, renderer: &Arc<Renderer>, factor: Vec3) -> bool {
+        let shape = self.shape.resized(factor);
+        let mesh = match shape.mesh(self.texture_scale) {
+            Some(mesh) => mesh,
+            None => return false,
+        };
+        let mesh_handle =
+            stats::timed(Op::AddMesh, || renderer.add_mesh(mesh)).expect("Error adding mesh");
+        let object = add_object(renderer, mesh_handle, &self.material, self.transform);
+        let old = std::mem::replace(&mut self.object, object);
+        stats::timed(Op::DropObject, || drop(old));
+        self.shape = shape;
+        true
+    }
+
+    /// Make again from scratch: new mesh, material, and object. False if not possible.
+    //  For comparison with changing objects in place. A shared material is
+    //  kept, as a new object would get it from the cache, so the objects
+    //  sharing it still share it afterwards.
+    pub fn recreate(&mut self, renderer: &Arc<Renderer>) -> bool {
+        let mesh = match self.shape.mesh(self.texture_scale) {
+            Some(mesh) => mesh,
+            None => return false,
+        };
+        if !self.shared {
+            self.material = create_simple_material(renderer, &self.textures, &self.surface);
+        }
+        let mesh_handle =
+            stats::timed(Op::AddMesh, || renderer.add_mesh(mesh)).expect("Error adding mesh");
+        let object = add_object(renderer, mesh_handle, &self.material, self.transform);
+        let old = std::mem::replace(&mut self.object, object);
+        stats::timed(Op::DropObject, || drop(old));
+        true
+    }
+}
+
 /// Very simple texture, but a bit of shinyness.
+//  The surface is plain unless materials vary, and opaque except for glass.
 pub fn create_simple_material(
     renderer: &Arc<Renderer>,
-    albedo_handle: &Texture2DHandle,
-    normal_handle: &Texture2DHandle,
+    textures: &MaterialTextures,
+    surface: &Surface,
 ) -> MaterialHandle {
     profiling::scope!("Add material");
-    let diffuse_color = Vec4::ONE; // white
-                                   //  Albedo from texture
+    let pbr_material = simple_pbr_material(textures, surface);
+    stats::timed(Op::AddMaterial, || renderer.add_material(pbr_material)) // add material to Rend3 system
+}
+
+/// The material used by create_simple_material, with albedo tinted per the surface.
+//  With an ambient occlusion, roughness, metallic texture, the surface's
+//  factors scale what it says, as in glTF.
+fn simple_pbr_material(
+    (albedo_handle, normal_handle, aomr_handle): &MaterialTextures,
+    surface: &Surface,
+) -> PbrMaterial {
+    //  Albedo from texture
     let albedo = AlbedoComponent::TextureValue {
         texture: albedo_handle.clone(),
-        value: diffuse_color,
+        value: surface.tint,
     };
     let normal = NormalTexture::Tricomponent(normal_handle.clone(), Default::default());
-    let pbr_material = PbrMaterial {
+    let emissive = if surface.emissive == Vec3::ZERO {
+        MaterialComponent::None
+    } else {
+        MaterialComponent::Value(surface.emissive)
+    };
+    let aomr_textures = match aomr_handle {
+        Some(texture) => AoMRTextures::Combined {
+            texture: Some(texture.clone()),
+        },
+        None => AoMRTextures::None,
+    };
+    PbrMaterial {
         albedo,
+        transparency: surface.transparency,
         normal,
-        ////aomr_textures,
+        aomr_textures,
         ao_factor: Some(1.0),
-        metallic_factor: Some(0.2),
-        roughness_factor: Some(0.2), // ***TEMP TEST***
-        uv_transform0: Mat3::IDENTITY,
+        metallic_factor: Some(surface.metallic),
+        roughness_factor: Some(surface.roughness),
+        emissive,
+        uv_transform0: surface.uv_transform,
         uv_transform1: Mat3::IDENTITY, // not used yet
         ..Default::default()
-    };
-    renderer.add_material(pbr_material) // add material to Rend3 system
+    }
 }
 
 /// Read texture, get RGBA
@@ -93,11 +595,31 @@ pub fn create_texture_from_rgba(
     renderer: &Arc<Renderer>,
     label: &str,
     rgba: &RgbaImage,
+) -> Texture2DHandle {
+    add_texture_from_rgba(renderer, label, rgba, TextureFormat::Rgba8UnormSrgb) // per WGPU tutorial
+}
+
+/// Create texture from RGBA data which is not color, such as occlusion and roughness.
+//  Linear, so values reach the shader as stored.
+pub fn create_data_texture_from_rgba(
+    renderer: &Arc<Renderer>,
+    label: &str,
+    rgba: &RgbaImage,
+) -> Texture2DHandle {
+    add_texture_from_rgba(renderer, label, rgba, TextureFormat::Rgba8Unorm)
+}
+
+/// Add texture in this format.
+fn add_texture_from_rgba(
+    renderer: &Arc<Renderer>,
+    label: &str,
+    rgba: &RgbaImage,
+    format: TextureFormat,
 ) -> Texture2DHandle {
     let mips = 1; // no mipmapping for now
     let texture = Texture {
         label: Some(label.to_string()),
-        format: TextureFormat::Rgba8UnormSrgb, // per WGPU tutorial
+        format,
         size: UVec2::new(rgba.width(), rgba.height()),
         data: rgba.clone().into_raw(),
         //// TODO: automatic mipmapping (#53)
@@ -105,22 +627,607 @@ pub fn create_texture_from_rgba(
         mip_source: rend3::types::MipmapSource::Uploaded,
     };
     profiling::scope!("Add texture");
-    renderer.add_texture_2d(texture).expect("Error adding texture") // put into GPU
+    stats::timed(Op::AddTexture, || renderer.add_texture_2d(texture)).expect("Error adding texture") // put into GPU
 }
 
 //  Create a mesh object with the appropriate scale and origin offset.
 pub fn create_mesh(scale: Vec3, offset: Vec3, texture_scale: f32) -> Mesh {
+    create_merged_mesh(&[(scale, offset)], texture_scale)
+}
+
+//  Create one mesh from several boxes, each with its own scale and origin offset.
+pub fn create_merged_mesh(parts: &[(Vec3, Vec3)], texture_scale: f32) -> Mesh {
     let mul_elements = |a: Vec3, b: Vec3| Vec3::new(a[0] * b[0], a[1] * b[1], a[2] * b[2]); // why is this never built into vec libraries?
-                                                                                            //  Scale and offset verts.
-    let vertex_positions: Vec<Vec3> = UNIT_CUBE_VERTS
+    let mut vertex_positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut indices = Vec::new();
+    for (scale, offset) in parts {
+        let base = vertex_positions.len() as u32; // index of first vert of this box
+        //  Scale and offset verts.
+        vertex_positions.extend(
+            UNIT_CUBE_VERTS
+                .iter()
+                .map(|v| mul_elements(*scale, (*v).into()) + *offset),
+        );
+        normals.extend(UNIT_CUBE_FACE_NORMALS.iter().map(|v| Vec3::from(*v)));
+        indices.extend(UNIT_CUBE_INDICES.iter().map(|i| i + base));
+    }
+    //  Create UVs.
+    let uvs = calc_uvs(&vertex_positions, &normals, texture_scale);
+    MeshBuilder::new(vertex_positions, rend3::types::Handedness::Left)
+        .with_indices(indices)
+        .with_vertex_normals(normals)
+        .with_vertex_texture_coordinates_0(uvs)
+        .build()
+        .unwrap()
+}
+
+/// Create a heightfield mesh, a grid of vertices displaced in Y.
+//  Vertices are relative to origin, but heights and UVs come from world
+//  coordinates, so adjacent tiles match at their edges.
+pub fn create_heightfield_mesh(
+    (size, resolution): (f32, usize),
+    height: &dyn Fn(Vec2) -> f32,
+    origin: Vec2,
+    texture_scale: f32,
+) -> Mesh {
+    let resolution = resolution.max(1);
+    let step = size / resolution as f32;
+    let mut vertex_positions = Vec::with_capacity((resolution + 1) * (resolution + 1));
+    let mut normals = Vec::with_capacity(vertex_positions.capacity());
+    let mut uvs = Vec::with_capacity(vertex_positions.capacity());
+    for j in 0..=resolution {
+        for i in 0..=resolution {
+            let local = Vec2::new(i as f32, j as f32) * step;
+            let world = origin + local;
+            vertex_positions.push(Vec3::new(local.x, height(world), local.y));
+            //  Normal from the slope, by central differences.
+            let slope = |d: Vec2| height(world + d) - height(world - d);
+            normals.push(
+                Vec3::new(
+                    -slope(Vec2::new(step, 0.0)),
+                    2.0 * step,
+                    -slope(Vec2::new(0.0, step)),
+                )
+                .normalize(),
+            );
+            uvs.push(calc_single_uv(world, 1.0) * texture_scale);
+        }
+    }
+    //  Two triangles per quad, wound as for the unit cube.
+    let row = resolution as u32 + 1;
+    let mut indices = Vec::with_capacity(resolution * resolution * 6);
+    for j in 0..resolution as u32 {
+        for i in 0..resolution as u32 {
+            let corner = j * row + i;
+            let quad = [corner, corner + 1, corner + row + 1, corner + row];
+            indices.extend([0, 3, 2, 2, 1, 0].map(|n| quad[n]));
+        }
+    }
+    MeshBuilder::new(vertex_positions, rend3::types::Handedness::Left)
+        .with_indices(indices)
+        .with_vertex_normals(normals)
+        .with_vertex_texture_coordinates_0(uvs)
+        .build()
+        .unwrap()
+}
+
+/// Parse tessellation from command line. 0 for square everything, else at least 3 sides.
+//  One or two sides make flat, degenerate round shapes.
+pub fn parse_tessellation(s: &str) -> Result<u32, &'static str> {
+    match s.parse::<u32>() {
+        Ok(n) if n == 0 || n >= 3 => Ok(n),
+        Ok(_) => Err("Tessellation must be 0, or at least 3 sides"),
+        Err(_) => Err("Tessellation must be a count of sides"),
+    }
+}
+
+/// Primitive shapes, after the Second Life prim types.
+//  Extruded shapes sweep a profile in the XZ plane along the Y axis.
+//  Revolved shapes sweep a profile in the (radius, Y) plane around the Y axis.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrimShape {
+    Box,      // square profile, extruded
+    Cylinder, // round profile, extruded
+    Prism,    // triangular profile, extruded
+    Wedge,    // right triangle profile, extruded
+    Sphere,   // half circle profile, revolved
+    Torus,    // round profile, revolved
+    Tube,     // square profile, revolved
+}
+
+/// How texture coordinates are generated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UvMapping {
+    Planar,      // projected along the dominant normal axis, as for blocks
+    Cylindrical, // wrapped around the Y axis on curved surfaces, planar elsewhere
+}
+
+/// A primitive solid, unit size before scaling.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Prim {
+    pub shape: PrimShape,  // basic shape
+    pub cut: (f32, f32),   // begin and end of path cut, as fractions of a turn around Y
+    pub hollow: f32,       // fraction of size hollowed out, 0 to 0.95
+    pub taper: f32,        // extruded shapes only: how much smaller the top is. 1.0 is a cone.
+    pub tessellation: u32, // sides for a full turn
+    pub uv: UvMapping,     // texture mapping
+}
+
+impl Prim {
+    /// Whole, solid prim, with the usual mapping for its shape.
+    pub fn new(shape: PrimShape, tessellation: u32) -> Prim {
+        Prim {
+            shape,
+            cut: (0.0, 1.0),
+            hollow: 0.0,
+            taper: 0.0,
+            tessellation,
+            uv: match shape {
+                PrimShape::Cylinder | PrimShape::Sphere | PrimShape::Torus | PrimShape::Tube => {
+                    UvMapping::Cylindrical
+                }
+                PrimShape::Box | PrimShape::Prism | PrimShape::Wedge => UvMapping::Planar,
+            },
+        }
+    }
+
+    /// Cone. A cylinder tapered to a point.
+    pub fn cone(tessellation: u32) -> Prim {
+        Prim {
+            taper: 1.0,
+            ..Prim::new(PrimShape::Cylinder, tessellation)
+        }
+    }
+
+    /// Everything which determines the mesh, for sharing.
+    fn key(&self) -> [u32; 7] {
+        [
+            self.shape as u32,
+            self.cut.0.to_bits(),
+            self.cut.1.to_bits(),
+            self.hollow.to_bits(),
+            self.taper.to_bits(),
+            self.tessellation,
+            self.uv as u32,
+        ]
+    }
+
+    /// Angles of cut, in radians, and whether there is a cut at all.
+    fn cut_angles(&self) -> (f32, f32, bool) {
+        let begin = self.cut.0.clamp(0.0, 1.0);
+        let end = self.cut.1.clamp(begin + 0.01, 1.0);
+        (begin * TAU, end * TAU, end - begin < 1.0)
+    }
+
+    /// Number of steps covering the cut part of a full turn.
+    fn steps(&self, min: u32) -> u32 {
+        let (begin, end, _) = self.cut_angles();
+        ((self.tessellation as f32 * (end - begin) / TAU).ceil() as u32).max(min)
+    }
+}
+
+/// One edge of a 2D profile, with normals at each end.
+#[derive(Debug, Copy, Clone)]
+struct ProfileEdge {
+    a: Vec2,
+    b: Vec2,
+    na: Vec2,
+    nb: Vec2,
+    curved: bool, // part of a round surface, for cylindrical UVs
+}
+
+/// A 2D profile, to be extruded or revolved.
+struct Profile {
+    center: Vec2,                // fan center for end caps when not hollow
+    outer: Vec<ProfileEdge>,     // outline
+    inner: Vec<ProfileEdge>,     // hollow outline, matching outer edge for edge, or empty
+    cut_faces: Vec<ProfileEdge>, // flat faces where the profile is cut, extruded only
+}
+
+impl Profile {
+    /// Closed profile from points along a curve, with normals, plus hollow.
+    fn from_points(center: Vec2, points: &[(Vec2, Vec2)], curved: bool, hollow: f32) -> Profile {
+        let outer: Vec<ProfileEdge> = points
+            .windows(2)
+            .map(|pair| ProfileEdge {
+                a: pair[0].0,
+                b: pair[1].0,
+                na: pair[0].1,
+                nb: pair[1].1,
+                curved,
+            })
+            .collect();
+        Profile {
+            center,
+            inner: hollow_edges(center, &outer, hollow),
+            outer,
+            cut_faces: Vec::new(),
+        }
+    }
+}
+
+/// Edges of the hollow, matching the outline, facing inward.
+fn hollow_edges(center: Vec2, outer: &[ProfileEdge], hollow: f32) -> Vec<ProfileEdge> {
+    let hollow = hollow.clamp(0.0, 0.95);
+    if hollow <= 0.0 {
+        return Vec::new();
+    }
+    outer
         .iter()
-        .map(|v| mul_elements(scale, (*v).into()) + offset)
+        .map(|edge| ProfileEdge {
+            a: center + (edge.a - center) * hollow,
+            b: center + (edge.b - center) * hollow,
+            na: -edge.na,
+            nb: -edge.nb,
+            curved: edge.curved,
+        })
+        .collect()
+}
+
+/// Profile for an extruded shape, cut around its center.
+//  Round profiles are sampled evenly. Polygon profiles are sampled at the
+//  cut angles and at each corner between them, so each edge is flat.
+fn extrusion_profile(prim: &Prim) -> Profile {
+    let (begin, end, is_cut) = prim.cut_angles();
+    let dir = |angle: f32| Vec2::new(angle.cos(), angle.sin());
+    let corners: &[Vec2] = match prim.shape {
+        PrimShape::Prism => &[
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, -0.5),
+            Vec2::new(0.0, 0.5),
+        ],
+        PrimShape::Wedge => &[
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, -0.5),
+            Vec2::new(-0.5, 0.5),
+        ],
+        _ => &[
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, -0.5),
+            Vec2::new(0.5, 0.5),
+            Vec2::new(-0.5, 0.5),
+        ],
+    };
+    let mut profile = if prim.shape == PrimShape::Cylinder {
+        let steps = prim.steps(1);
+        let points: Vec<(Vec2, Vec2)> = (0..=steps)
+            .map(|n| {
+                let normal = dir(begin + (end - begin) * n as f32 / steps as f32);
+                (normal * 0.5, normal)
+            })
+            .collect();
+        Profile::from_points(Vec2::ZERO, &points, true, prim.hollow)
+    } else {
+        let center = corners.iter().copied().sum::<Vec2>() / corners.len() as f32;
+        //  Sample angles: cut begin, corners in between, cut end.
+        let corner_angle = |corner: Vec2| {
+            let offset = corner - center;
+            offset.y.atan2(offset.x).rem_euclid(TAU)
+        };
+        let mut angles: Vec<f32> = corners
+            .iter()
+            .map(|corner| corner_angle(*corner))
+            .filter(|angle| *angle > begin && *angle < end)
+            .collect();
+        angles.push(begin);
+        angles.push(end);
+        angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let points: Vec<Vec2> = angles
+            .iter()
+            .map(|angle| polygon_point(corners, center, dir(*angle)))
+            .collect();
+        let outer: Vec<ProfileEdge> = points
+            .windows(2)
+            .filter(|pair| pair[0].distance(pair[1]) > 1e-5)
+            .map(|pair| {
+                let side = pair[1] - pair[0];
+                let mut normal = Vec2::new(side.y, -side.x).normalize();
+                if normal.dot((pair[0] + pair[1]) * 0.5 - center) < 0.0 {
+                    normal = -normal;
+                }
+                ProfileEdge {
+                    a: pair[0],
+                    b: pair[1],
+                    na: normal,
+                    nb: normal,
+                    curved: false,
+                }
+            })
+            .collect();
+        Profile {
+            center,
+            inner: hollow_edges(center, &outer, prim.hollow),
+            outer,
+            cut_faces: Vec::new(),
+        }
+    };
+    //  Flat faces where cut, from the hollow or center out to the outline.
+    if is_cut {
+        let first = profile.outer.first().unwrap();
+        let last = profile.outer.last().unwrap();
+        let (inner_first, inner_last) = match (profile.inner.first(), profile.inner.last()) {
+            (Some(first), Some(last)) => (first.a, last.b),
+            _ => (profile.center, profile.center),
+        };
+        let begin_normal = Vec2::new(begin.sin(), -begin.cos()); // facing away from the solid part
+        let end_normal = Vec2::new(-end.sin(), end.cos());
+        profile.cut_faces = vec![
+            ProfileEdge {
+                a: inner_first,
+                b: first.a,
+                na: begin_normal,
+                nb: begin_normal,
+                curved: false,
+            },
+            ProfileEdge {
+                a: inner_last,
+                b: last.b,
+                na: end_normal,
+                nb: end_normal,
+                curved: false,
+            },
+        ];
+    }
+    profile
+}
+
+/// Where a ray from center in direction dir leaves a convex polygon.
+fn polygon_point(corners: &[Vec2], center: Vec2, dir: Vec2) -> Vec2 {
+    let cross = |a: Vec2, b: Vec2| a.x * b.y - a.y * b.x;
+    let mut nearest = f32::MAX;
+    for (n, p) in corners.iter().enumerate() {
+        let q = corners[(n + 1) % corners.len()];
+        let side = q - *p;
+        let denom = cross(dir, side);
+        if denom.abs() < 1e-9 {
+            continue; // parallel
+        }
+        let t = cross(*p - center, side) / denom; // distance along ray
+        let s = cross(*p - center, dir) / denom; // fraction along side
+        if t > 0.0 && (-1e-5..=1.0 + 1e-5).contains(&s) {
+            nearest = nearest.min(t);
+        }
+    }
+    center + dir * nearest
+}
+
+/// Profile for a revolved shape, in the (radius, Y) plane.
+fn revolution_profile(prim: &Prim) -> Profile {
+    let rings = (prim.tessellation / 2).max(2);
+    let ring_angle = |n: u32| n as f32 / rings as f32;
+    match prim.shape {
+        PrimShape::Sphere => {
+            //  Half circle from bottom pole to top pole.
+            let points: Vec<(Vec2, Vec2)> = (0..=rings)
+                .map(|n| {
+                    let angle = PI * (ring_angle(n) - 0.5);
+                    let normal = Vec2::new(angle.cos(), angle.sin());
+                    (normal * 0.5, normal)
+                })
+                .collect();
+            Profile::from_points(Vec2::ZERO, &points, true, prim.hollow)
+        }
+        PrimShape::Torus => {
+            //  Ellipse filling the ring from radius 0.25 to 0.5, full height.
+            const RADII: Vec2 = Vec2::new(0.125, 0.5);
+            let center = Vec2::new(0.375, 0.0);
+            let points: Vec<(Vec2, Vec2)> = (0..=rings * 2)
+                .map(|n| {
+                    let angle = TAU * n as f32 / (rings * 2) as f32;
+                    let unit = Vec2::new(angle.cos(), angle.sin());
+                    (center + unit * RADII, (unit / RADII).normalize())
+                })
+                .collect();
+            Profile::from_points(center, &points, true, prim.hollow)
+        }
+        _ => {
+            //  Tube. Square filling the ring from radius 0.25 to 0.5, full height.
+            let center = Vec2::new(0.375, 0.0);
+            let corners = [
+                Vec2::new(0.25, -0.5),
+                Vec2::new(0.5, -0.5),
+                Vec2::new(0.5, 0.5),
+                Vec2::new(0.25, 0.5),
+                Vec2::new(0.25, -0.5),
+            ];
+            let outer: Vec<ProfileEdge> = corners
+                .windows(2)
+                .map(|pair| {
+                    let side = pair[1] - pair[0];
+                    let mut normal = Vec2::new(side.y, -side.x).normalize();
+                    if normal.dot((pair[0] + pair[1]) * 0.5 - center) < 0.0 {
+                        normal = -normal;
+                    }
+                    ProfileEdge {
+                        a: pair[0],
+                        b: pair[1],
+                        na: normal,
+                        nb: normal,
+                        curved: normal.x.abs() > 0.5, // inner and outer walls are round
+                    }
+                })
+                .collect();
+            Profile {
+                center,
+                inner: hollow_edges(center, &outer, prim.hollow),
+                outer,
+                cut_faces: Vec::new(),
+            }
+        }
+    }
+}
+
+/// Mesh under construction. Triangles are wound to face their normals.
+#[derive(Default)]
+struct MeshParts {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    angles: Vec<Option<f32>>, // angle around Y, for vertices on round surfaces
+    indices: Vec<u32>,
+}
+
+/// A vertex: position, normal, and angle around Y if on a round surface.
+type PrimVertex = (Vec3, Vec3, Option<f32>);
+
+impl MeshParts {
+    /// Add a quad, or a triangle if two corners coincide.
+    fn quad(&mut self, verts: [PrimVertex; 4]) {
+        let base = self.positions.len() as u32;
+        let [p0, p1, p2, p3] = verts.map(|v| v.0);
+        let normal: Vec3 = verts.iter().map(|v| v.1).sum();
+        let facing = (p2 - p0).cross(p3 - p1).dot(normal);
+        for (position, normal, angle) in verts {
+            self.positions.push(position);
+            self.normals.push(normal);
+            self.angles.push(angle);
+        }
+        let order: [u32; 6] = if facing >= 0.0 {
+            [0, 1, 2, 2, 3, 0]
+        } else {
+            [0, 3, 2, 2, 1, 0]
+        };
+        self.indices.extend(order.iter().map(|i| i + base));
+    }
+
+    /// Add a triangle.
+    fn triangle(&mut self, verts: [PrimVertex; 3]) {
+        self.quad([verts[0], verts[1], verts[2], verts[2]]);
+    }
+
+    /// Fill a profile at an end: fan from center, or band to the hollow.
+    fn fill_profile(&mut self, profile: &Profile, place: impl Fn(Vec2) -> Vec3, normal: Vec3) {
+        let vert = |p: Vec2| (place(p), normal, None);
+        if profile.inner.is_empty() {
+            for edge in &profile.outer {
+                self.triangle([vert(profile.center), vert(edge.a), vert(edge.b)]);
+            }
+        } else {
+            for (outer, inner) in profile.outer.iter().zip(&profile.inner) {
+                self.quad([vert(outer.a), vert(outer.b), vert(inner.b), vert(inner.a)]);
+            }
+        }
+    }
+}
+
+/// Extrude a profile along Y, from -0.5 to 0.5, tapering toward the top.
+fn extrude(prim: &Prim, profile: &Profile, parts: &mut MeshParts) {
+    let taper = prim.taper.clamp(0.0, 1.0);
+    let top = |p: Vec2| profile.center + (p - profile.center) * (1.0 - taper);
+    let place = |p: Vec2, y: f32| Vec3::new(p.x, y, p.y);
+    //  Side normals tilt upward as the sides lean in.
+    let side_normal = |p: Vec2, n: Vec2| {
+        Vec3::new(n.x, taper * (p - profile.center).dot(n), n.y).normalize()
+    };
+    let angle = |edge: &ProfileEdge, p: Vec2| {
+        if edge.curved {
+            Some((p - profile.center).y.atan2((p - profile.center).x).rem_euclid(TAU))
+        } else {
+            None
+        }
+    };
+    for edge in profile
+        .outer
+        .iter()
+        .chain(&profile.inner)
+        .chain(&profile.cut_faces)
+    {
+        //  Angles must not wrap within a quad, or the texture would too.
+        let angle_a = angle(edge, edge.a);
+        let angle_b = match (angle_a, angle(edge, edge.b)) {
+            (Some(a), Some(b)) if b < a - PI => Some(b + TAU),
+            (_, b) => b,
+        };
+        parts.quad([
+            (place(edge.a, -0.5), side_normal(edge.a, edge.na), angle_a),
+            (place(edge.b, -0.5), side_normal(edge.b, edge.nb), angle_b),
+            (place(top(edge.b), 0.5), side_normal(edge.b, edge.nb), angle_b),
+            (place(top(edge.a), 0.5), side_normal(edge.a, edge.na), angle_a),
+        ]);
+    }
+    parts.fill_profile(profile, |p| place(p, -0.5), Vec3::NEG_Y);
+    if taper < 1.0 {
+        parts.fill_profile(profile, |p| place(top(p), 0.5), Vec3::Y);
+    }
+}
+
+/// Revolve a profile around Y, through the cut.
+fn revolve(prim: &Prim, profile: &Profile, parts: &mut MeshParts) {
+    let (begin, end, is_cut) = prim.cut_angles();
+    let steps = prim.steps(3);
+    let place = |p: Vec2, angle: f32| Vec3::new(p.x * angle.cos(), p.y, p.x * angle.sin());
+    let turn = |n: Vec2, angle: f32| Vec3::new(n.x * angle.cos(), n.y, n.x * angle.sin());
+    for edge in profile.outer.iter().chain(&profile.inner) {
+        for step in 0..steps {
+            let a0 = begin + (end - begin) * step as f32 / steps as f32;
+            let a1 = begin + (end - begin) * (step + 1) as f32 / steps as f32;
+            let uv_angle = |angle: f32| if edge.curved { Some(angle) } else { None };
+            parts.quad([
+                (place(edge.a, a0), turn(edge.na, a0), uv_angle(a0)),
+                (place(edge.b, a0), turn(edge.nb, a0), uv_angle(a0)),
+                (place(edge.b, a1), turn(edge.nb, a1), uv_angle(a1)),
+                (place(edge.a, a1), turn(edge.na, a1), uv_angle(a1)),
+            ]);
+        }
+    }
+    //  Ends, where cut.
+    if is_cut {
+        parts.fill_profile(
+            profile,
+            |p| place(p, begin),
+            Vec3::new(begin.sin(), 0.0, -begin.cos()),
+        );
+        parts.fill_profile(
+            profile,
+            |p| place(p, end),
+            Vec3::new(-end.sin(), 0.0, end.cos()),
+        );
+    }
+}
+
+/// Unit size triangles, normals and round surface angles for a prim.
+fn prim_parts(prim: &Prim) -> MeshParts {
+    let mut parts = MeshParts::default();
+    match prim.shape {
+        PrimShape::Box | PrimShape::Cylinder | PrimShape::Prism | PrimShape::Wedge => {
+            extrude(prim, &extrusion_profile(prim), &mut parts)
+        }
+        PrimShape::Sphere | PrimShape::Torus | PrimShape::Tube => {
+            revolve(prim, &revolution_profile(prim), &mut parts)
+        }
+    }
+    parts
+}
+
+/// Create a mesh for a prim, with the appropriate scale and origin offset.
+pub fn create_prim_mesh(prim: &Prim, scale: Vec3, offset: Vec3, texture_scale: f32) -> Mesh {
+    let parts = prim_parts(prim);
+    //  Scale, offset, and fix normals for the scaling.
+    let vertex_positions: Vec<Vec3> = parts
+        .positions
+        .iter()
+        .map(|v| *v * scale + offset)
         .collect();
-    let normals: Vec<Vec3> = UNIT_CUBE_FACE_NORMALS.iter().map(|v| (*v).into()).collect();
-    let uvs = calc_uvs(&vertex_positions, &normals, texture_scale);
-    //  Create UVs.
-    MeshBuilder::new(vertex_positions.to_vec(), rend3::types::Handedness::Left)
-        .with_indices(UNIT_CUBE_INDICES.to_vec())
+    let normals: Vec<Vec3> = parts
+        .normals
+        .iter()
+        .map(|n| (*n / scale).normalize_or_zero())
+        .collect();
+    //  UVs
+    let radius = 0.5 * scale.x.max(scale.z); // for cylindrical mapping
+    let uvs: Vec<Vec2> = vertex_positions
+        .iter()
+        .zip(&normals)
+        .zip(&parts.angles)
+        .map(|((v, n), angle)| match (prim.uv, angle) {
+            (UvMapping::Cylindrical, Some(angle)) => {
+                calc_single_uv(Vec2::new(angle * radius, v.y), 1.0) * texture_scale
+            }
+            _ => calc_uv(norm_to_axis(n), v, n) * texture_scale,
+        })
+        .collect();
+    MeshBuilder::new(vertex_positions, rend3::types::Handedness::Left)
+        .with_indices(parts.indices)
         .with_vertex_normals(normals)
         .with_vertex_texture_coordinates_0(uvs)
         .build()
@@ -251,3 +1358,150 @@ const UNIT_CUBE_INDICES: [u32; 36] = [
         .unwrap()
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Volume enclosed, from the triangles as wound. Positive if they face outward.
+    fn volume(parts: &MeshParts) -> f32 {
+        parts
+            .indices
+            .chunks(3)
+            .map(|tri| {
+                let [a, b, c] = [0, 1, 2].map(|n| parts.positions[tri[n] as usize]);
+                a.dot(b.cross(c)) / 6.0
+            })
+            .sum()
+    }
+
+    /// Every triangle is wound to face the way its vertex normals point.
+    fn assert_wound_to_normals(parts: &MeshParts) {
+        for tri in parts.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|n| parts.positions[tri[n] as usize]);
+            let face = (b - a).cross(c - a);
+            if face.length() < 1e-6 {
+                continue; // degenerate, as at a pole or a cone's tip
+            }
+            let normal: Vec3 = tri.iter().map(|i| parts.normals[*i as usize]).sum();
+            assert!(
+                face.dot(normal) > 0.0,
+                "triangle {:?} faces away from its normals",
+                tri
+            );
+        }
+    }
+
+    /// Area of a regular polygon with this many sides, circumscribed by a unit diameter circle.
+    fn polygon_area(sides: u32) -> f32 {
+        0.125 * sides as f32 * (TAU / sides as f32).sin()
+    }
+
+    /// Check quad count, winding and volume.
+    fn check(prim: Prim, quads: usize, expected_volume: f32, tolerance: f32) {
+        let parts = prim_parts(&prim);
+        assert_eq!(parts.positions.len(), quads * 4, "{:?} vertices", prim);
+        assert_eq!(parts.normals.len(), parts.positions.len());
+        assert_eq!(parts.angles.len(), parts.positions.len());
+        assert_eq!(parts.indices.len(), quads * 6, "{:?} indices", prim);
+        assert_wound_to_normals(&parts);
+        let v = volume(&parts);
+        assert!(
+            (v - expected_volume).abs() <= expected_volume * tolerance,
+            "{:?} volume {} is not near {}",
+            prim,
+            v,
+            expected_volume
+        );
+    }
+
+    #[test]
+    fn extruded_shapes() {
+        //  Sides, bottom and top. Polygon sides are split where the sampling starts, at angle 0.
+        check(Prim::new(PrimShape::Box, 8), 5 * 3, 1.0, 1e-4);
+        check(Prim::new(PrimShape::Prism, 8), 4 * 3, 0.5, 1e-4);
+        check(Prim::new(PrimShape::Wedge, 8), 4 * 3, 0.5, 1e-4);
+        check(
+            Prim::new(PrimShape::Cylinder, 8),
+            8 * 3,
+            polygon_area(8),
+            1e-4,
+        );
+        //  A cone has no top.
+        check(Prim::cone(8), 8 * 2, polygon_area(8) / 3.0, 1e-4);
+    }
+
+    #[test]
+    fn revolved_shapes() {
+        //  Profile edges times steps around. No ends unless cut.
+        check(Prim::new(PrimShape::Sphere, 64), 32 * 64, PI / 6.0, 0.01);
+        let torus_volume = TAU * 0.375 * PI * 0.125 * 0.5; // Pappus
+        check(Prim::new(PrimShape::Torus, 64), 64 * 64, torus_volume, 0.01);
+        let tube_volume = TAU * 0.375 * 0.25;
+        check(Prim::new(PrimShape::Tube, 64), 4 * 64, tube_volume, 0.01);
+    }
+
+    #[test]
+    fn hollow() {
+        let hollow = |shape| Prim {
+            hollow: 0.5,
+            ..Prim::new(shape, 8)
+        };
+        //  Outer and inner sides, and a band at each end.
+        check(hollow(PrimShape::Box), 5 * 4, 0.75, 1e-4);
+        check(
+            hollow(PrimShape::Cylinder),
+            8 * 4,
+            polygon_area(8) * 0.75,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn path_cut() {
+        let cut = |shape, end| Prim {
+            cut: (0.0, end),
+            ..Prim::new(shape, 8)
+        };
+        //  Sides, two cut faces, and the ends.
+        check(cut(PrimShape::Box, 0.5), 3 + 2 + 3 * 2, 0.5, 1e-4);
+        check(cut(PrimShape::Box, 0.25), 2 + 2 + 2 * 2, 0.25, 1e-4);
+        check(
+            cut(PrimShape::Cylinder, 0.25),
+            2 + 2 + 2 * 2,
+            polygon_area(8) * 0.25,
+            1e-4,
+        );
+        //  Revolved shapes get a filled profile at each end of the cut.
+        let whole = volume(&prim_parts(&Prim::new(PrimShape::Sphere, 8)));
+        check(
+            cut(PrimShape::Sphere, 0.5),
+            4 * 4 + 4 * 2,
+            whole / 2.0,
+            1e-3,
+        );
+        //  Cut and hollow together.
+        let hollow_cut = Prim {
+            hollow: 0.5,
+            ..cut(PrimShape::Cylinder, 0.5)
+        };
+        check(
+            hollow_cut,
+            4 * 2 + 2 + 4 * 2,
+            polygon_area(8) * 0.75 * 0.5,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn uv_mapping() {
+        assert_eq!(Prim::new(PrimShape::Box, 8).uv, UvMapping::Planar);
+        assert_eq!(Prim::new(PrimShape::Wedge, 8).uv, UvMapping::Planar);
+        assert_eq!(Prim::new(PrimShape::Tube, 8).uv, UvMapping::Cylindrical);
+        //  Only round surfaces have angles for cylindrical mapping.
+        let parts = prim_parts(&Prim::new(PrimShape::Box, 8));
+        assert!(parts.angles.iter().all(|angle| angle.is_none()));
+        let parts = prim_parts(&Prim::new(PrimShape::Cylinder, 8));
+        assert!(parts.angles.iter().any(|angle| angle.is_some()));
+    }
+}
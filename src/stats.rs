@@ -0,0 +1,428 @@
+//  stats.rs -- timing statistics for Rend3 operations.
+//
+//  Part of render-bench.
+//
+//  Every call into Rend3 made by the content creation threads is timed,
+//  and the times are accumulated into one histogram per operation.
+//  Each thread has its own histograms, reported and cleared at the end of
+//  each of its builder phases, so we can see which call blocks, and how its
+//  cost grows with scene size, without attaching a profiler. With several
+//  builder threads, one thread's report does not take the others' calls.
+//
+//  Also the frame hitch detector, which logs frames much slower than
+//  the recent median, along with what the builder was doing at the time.
+//
+//  And GPU pass timing, aggregated from the wgpu-profiler results
+//  returned by each frame's render graph. With split-screen views, each
+//  view's graph is timed on its own, on the CPU and the GPU, and the
+//  frame's results are accumulated once.
+//
+use anyhow::{Context, Error};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use wgpu_profiler::GpuTimerScopeResult;
+
+/// The Rend3 operations we time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    AddMesh,
+    AddMaterial,
+    AddTexture,
+    AddTextureCube,
+    AddObject,
+    DropObject,
+    SetTransform,
+    UpdateMaterial,
+    AddLight,
+    DropLight,
+    UpdateLight,
+}
+
+impl Op {
+    /// All the operations, in report order.
+    pub const ALL: [Op; 11] = [
+        Op::AddMesh,
+        Op::AddMaterial,
+        Op::AddTexture,
+        Op::AddTextureCube,
+        Op::AddObject,
+        Op::DropObject,
+        Op::SetTransform,
+        Op::UpdateMaterial,
+        Op::AddLight,
+        Op::DropLight,
+        Op::UpdateLight,
+    ];
+
+    /// Name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::AddMesh => "add_mesh",
+            Op::AddMaterial => "add_material",
+            Op::AddTexture => "add_texture_2d",
+            Op::AddTextureCube => "add_texture_cube",
+            Op::AddObject => "add_object",
+            Op::DropObject => "drop object",
+            Op::SetTransform => "set_object_transform",
+            Op::UpdateMaterial => "update_material",
+            Op::AddLight => "add_point_light",
+            Op::DropLight => "drop point light",
+            Op::UpdateLight => "update_directional_light",
+        }
+    }
+}
+
+/// Per-operation call counts, shared by all threads.
+pub struct OpStats {
+    call_counts: Vec<AtomicU64>, // total calls since start, indexed by Op
+}
+
+thread_local! {
+    /// Per-operation latency histograms, in nanoseconds, for this thread's calls.
+    static OP_HISTOGRAMS: RefCell<Vec<histogram::Histogram>> =
+        RefCell::new(Op::ALL.iter().map(|_| histogram::Histogram::new()).collect());
+}
+
+impl OpStats {
+    /// Usual new
+    fn new() -> OpStats {
+        OpStats {
+            call_counts: Op::ALL.iter().map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Total calls of this operation since startup, on all threads. Never cleared.
+    pub fn call_count(&self, op: Op) -> u64 {
+        self.call_counts[op as usize].load(Ordering::Relaxed)
+    }
+
+    /// Record one call, made by this thread.
+    pub fn record(&self, op: Op, elapsed: Duration) {
+        self.call_counts[op as usize].fetch_add(1, Ordering::Relaxed);
+        OP_HISTOGRAMS.with(|histograms| {
+            let _ = histograms.borrow_mut()[op as usize].increment(elapsed.as_nanos() as u64);
+            // only fails for calls over 60 seconds
+        });
+    }
+
+    /// Has this thread made any calls since its last report?
+    pub fn used(&self) -> bool {
+        OP_HISTOGRAMS.with(|histograms| histograms.borrow().iter().any(|hist| hist.entries() > 0))
+    }
+
+    /// Print stats for all operations this thread used during the phase, then clear them for its next phase.
+    pub fn report(&self, phase: &str) {
+        println!("Rend3 call times for phase \"{}\":", phase);
+        OP_HISTOGRAMS.with(|histograms| {
+            for (op, hist) in Op::ALL.iter().zip(histograms.borrow_mut().iter_mut()) {
+                let count = hist.entries();
+                if count == 0 {
+                    continue;
+                } // not used this phase
+                let usecs = |nsecs: u64| nsecs as f32 / 1_000.0; // ns to us
+                println!(
+                    "  {:<24} {:>7} calls. \
+                    Min: {:0>5.2}us; \
+                    Average: {:0>5.2}us; \
+                    95%: {:0>5.2}us; \
+                    99%: {:0>5.2}us; \
+                    Max: {:0>5.2}us; \
+                    Total: {:0>5.2}ms",
+                    op.name(),
+                    count,
+                    usecs(hist.minimum().unwrap()),
+                    usecs(hist.mean().unwrap()),
+                    usecs(hist.percentile(95.0).unwrap()),
+                    usecs(hist.percentile(99.0).unwrap()),
+                    usecs(hist.maximum().unwrap()),
+                    usecs(hist.mean().unwrap()) * count as f32 / 1_000.0,
+                );
+                hist.clear();
+            }
+        });
+    }
+}
+
+/// The one set of operation call counts, shared by all threads.
+pub fn op_stats() -> &'static OpStats {
+    static OP_STATS: OnceLock<OpStats> = OnceLock::new();
+    OP_STATS.get_or_init(OpStats::new)
+}
+
+/// Time one Rend3 call.
+pub fn timed<T>(op: Op, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    op_stats().record(op, start.elapsed());
+    result
+}
+
+/// Frame hitch detector.
+//  A hitch is a frame which takes more than some multiple of the median
+//  of recent frame times. Each hitch is written to a log file, with what
+//  the builder was doing and how many objects were added and removed since
+//  the previous frame, so hitches can be matched up with scene changes.
+pub struct HitchDetector {
+    factor: f32,                // hitch if over this times the median
+    recent: VecDeque<Duration>, // recent frame times, for median
+    start: Instant,             // time of startup, for timestamps
+    log: BufWriter<File>,       // hitch log
+    hitch_count: usize,         // hitches since last report
+    prev_added: u64,            // objects added as of previous frame
+    prev_removed: u64,          // objects removed as of previous frame
+}
+
+impl HitchDetector {
+    /// Frames in the rolling median
+    const WINDOW: usize = 120;
+    /// Don't check for hitches until we have this many frames.
+    const MIN_FRAMES: usize = 30;
+
+    /// New, with log file.
+    pub fn new(factor: f32, log_filename: &str) -> Result<HitchDetector, Error> {
+        let file = File::create(log_filename)
+            .with_context(|| format!("Hitch log file {}", log_filename))?;
+        let mut log = BufWriter::new(file);
+        writeln!(
+            log,
+            "time_secs,frame_ms,median_ms,phase,objects_added,objects_removed"
+        )?;
+        log.flush()?;
+        Ok(HitchDetector {
+            factor,
+            recent: VecDeque::with_capacity(Self::WINDOW),
+            start: Instant::now(),
+            log,
+            hitch_count: 0,
+            prev_added: 0,
+            prev_removed: 0,
+        })
+    }
+
+    /// Call once per frame. Returns true if this frame was a hitch.
+    pub fn frame(&mut self, now: Instant, frame_time: Duration, phase: &str) -> bool {
+        //  Objects added and removed since last frame.
+        let added = op_stats().call_count(Op::AddObject);
+        let removed = op_stats().call_count(Op::DropObject);
+        let added_this_frame = added - self.prev_added;
+        let removed_this_frame = removed - self.prev_removed;
+        self.prev_added = added;
+        self.prev_removed = removed;
+        //  Compare with median of recent frames, before adding this one.
+        let mut hitch = false;
+        if self.recent.len() >= Self::MIN_FRAMES {
+            let mut sorted: Vec<Duration> = self.recent.iter().copied().collect();
+            sorted.sort();
+            let median = sorted[sorted.len() / 2];
+            if frame_time.as_secs_f32() > median.as_secs_f32() * self.factor {
+                hitch = true;
+                self.hitch_count += 1;
+                let result = writeln!(
+                    self.log,
+                    "{:.3},{:.2},{:.2},{},{},{}",
+                    (now - self.start).as_secs_f64(),
+                    frame_time.as_secs_f64() * 1000.0,
+                    median.as_secs_f64() * 1000.0,
+                    phase,
+                    added_this_frame,
+                    removed_this_frame
+                )
+                .and_then(|_| self.log.flush()); // flush, so log survives abrupt exit
+                if let Err(e) = result {
+                    eprintln!("Error writing hitch log: {:?}", e);
+                }
+            }
+        }
+        if self.recent.len() >= Self::WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(frame_time);
+        hitch
+    }
+
+    /// Hitches since last call.
+    pub fn take_hitch_count(&mut self) -> usize {
+        std::mem::take(&mut self.hitch_count)
+    }
+}
+
+/// GPU timing per render pass.
+//  Each GPU timer scope is accumulated into a histogram, keyed by the
+//  path of scope labels, so GPU-side stalls can be told from CPU-side ones.
+//  Can also capture a window of frames and write it as a Chrome trace.
+pub struct GpuTimingStats {
+    histograms: BTreeMap<String, histogram::Histogram>, // per pass, in nanoseconds
+    trace_frames: usize,                                 // frames in one trace capture
+    trace_remaining: usize,                              // frames left to capture, 0 if not capturing
+    trace: Vec<GpuTimerScopeResult>,                     // captured frames so far
+    trace_filename: String,                              // where the trace goes
+}
+
+impl GpuTimingStats {
+    /// Usual new
+    pub fn new(trace_frames: usize, trace_filename: &str) -> GpuTimingStats {
+        GpuTimingStats {
+            histograms: BTreeMap::new(),
+            trace_frames,
+            trace_remaining: 0,
+            trace: Vec::new(),
+            trace_filename: trace_filename.to_string(),
+        }
+    }
+
+    /// Add one frame's worth of GPU timing results.
+    pub fn accumulate(&mut self, scopes: &[GpuTimerScopeResult]) {
+        self.accumulate_scopes("", scopes);
+        if self.trace_remaining > 0 {
+            self.trace.extend_from_slice(scopes);
+            self.trace_remaining -= 1;
+            if self.trace_remaining == 0 {
+                self.write_trace();
+            }
+        }
+    }
+
+    /// Accumulate scopes, recursively.
+    fn accumulate_scopes(&mut self, prefix: &str, scopes: &[GpuTimerScopeResult]) {
+        for scope in scopes {
+            let name = if prefix.is_empty() {
+                scope.label.clone()
+            } else {
+                format!("{}/{}", prefix, scope.label)
+            };
+            let nsecs = ((scope.time.end - scope.time.start) * 1.0e9).max(0.0) as u64;
+            let _ = self
+                .histograms
+                .entry(name.clone())
+                .or_default()
+                .increment(nsecs);
+            self.accumulate_scopes(&name, &scope.nested_scopes);
+        }
+    }
+
+    /// Print stats for each pass, then clear.
+    pub fn report(&mut self) {
+        for (name, hist) in self.histograms.iter_mut() {
+            if hist.entries() == 0 {
+                continue;
+            }
+            let msecs = |nsecs: u64| nsecs as f32 / 1_000_000.0; // ns to ms
+            println!(
+                "  GPU {}: \
+                Average: {:0>5.2}ms; \
+                95%: {:0>5.2}ms; \
+                99%: {:0>5.2}ms; \
+                Max: {:0>5.2}ms",
+                name,
+                msecs(hist.mean().unwrap()),
+                msecs(hist.percentile(95.0).unwrap()),
+                msecs(hist.percentile(99.0).unwrap()),
+                msecs(hist.maximum().unwrap()),
+            );
+            hist.clear();
+        }
+    }
+
+    /// Start capturing a trace, unless one is already being captured.
+    pub fn start_trace(&mut self) {
+        if self.trace_remaining > 0 || self.trace_frames == 0 {
+            return;
+        }
+        println!(
+            "Capturing gpu timing chrome trace of {} frames.",
+            self.trace_frames
+        );
+        self.trace.clear();
+        self.trace_remaining = self.trace_frames;
+    }
+
+    /// Write out captured trace, readable by chrome://tracing
+    fn write_trace(&mut self) {
+        println!(
+            "Outputing gpu timing chrome trace to {}",
+            self.trace_filename
+        );
+        if let Err(e) =
+            wgpu_profiler::chrometrace::write_chrometrace(Path::new(&self.trace_filename), &self.trace)
+        {
+            eprintln!("Error writing {}: {:?}", self.trace_filename, e);
+        }
+        self.trace.clear();
+    }
+}
+
+/// CPU and GPU time of each view, when the window is split into several.
+//  GPU time goes into GpuTimingStats, as a scope named for the view
+//  holding that view's passes. CPU time, to set up, evaluate and execute
+//  each view's render graph, is kept here.
+pub struct ViewTimingStats {
+    cpu: Vec<(&'static str, histogram::Histogram)>, // per view, in nanoseconds, in view order
+}
+
+impl ViewTimingStats {
+    /// Usual new
+    pub fn new() -> ViewTimingStats {
+        ViewTimingStats { cpu: Vec::new() }
+    }
+
+    /// Record the CPU time of one view's render.
+    pub fn record_cpu(&mut self, view: &'static str, elapsed: Duration) {
+        let hist = match self.cpu.iter().position(|(name, _)| *name == view) {
+            Some(n) => &mut self.cpu[n].1,
+            None => {
+                self.cpu.push((view, histogram::Histogram::new()));
+                &mut self.cpu.last_mut().unwrap().1
+            }
+        };
+        let _ = hist.increment(elapsed.as_nanos() as u64);
+    }
+
+    /// One view's GPU scopes, inside one scope named for the view, spanning them all.
+    pub fn gpu_scope(view: &str, scopes: Vec<GpuTimerScopeResult>) -> Option<GpuTimerScopeResult> {
+        let first = scopes.first()?;
+        let start = scopes
+            .iter()
+            .map(|scope| scope.time.start)
+            .fold(f64::MAX, f64::min);
+        let end = scopes
+            .iter()
+            .map(|scope| scope.time.end)
+            .fold(f64::MIN, f64::max);
+        Some(GpuTimerScopeResult {
+            label: format!("view {}", view),
+            pid: first.pid,
+            tid: first.tid,
+            time: start..end,
+            nested_scopes: scopes,
+        })
+    }
+
+    /// Print CPU time for each view, then clear.
+    pub fn report(&mut self) {
+        for (name, hist) in self.cpu.iter_mut() {
+            if hist.entries() == 0 {
+                continue;
+            }
+            let msecs = |nsecs: u64| nsecs as f32 / 1_000_000.0; // ns to ms
+            println!(
+                "  CPU view {}: \
+                Average: {:0>5.2}ms; \
+                95%: {:0>5.2}ms; \
+                99%: {:0>5.2}ms; \
+                Max: {:0>5.2}ms",
+                name,
+                msecs(hist.mean().unwrap()),
+                msecs(hist.percentile(95.0).unwrap()),
+                msecs(hist.percentile(99.0).unwrap()),
+                msecs(hist.maximum().unwrap()),
+            );
+            hist.clear();
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//  sweep.rs -- run the benchmark over a matrix of settings.
+//
+//  Part of render-bench.
+//
+//  Usage: render-bench sweep --msaa 1,4 --profile cpu,gpu [--duration <secs>] [-- other options]
+//
+//  Runs the benchmark once for each combination of the given settings,
+//  by re-executing this program with --flythrough, --duration and --results,
+//  so every run sees the same scenario and camera path. Then collects
+//  all the results into one table.
+//
+use super::results::{BenchResults, PhaseGroup};
+use anyhow::{anyhow, Context, Error};
+use pico_args::Arguments;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::Write;
+use std::process::Command;
+
+const HELP: &str = "\
+render-bench sweep
+
+Run the benchmark for each combination of settings, and tabulate the results.
+
+usage: render-bench sweep --axes --options [-- options for every run]
+
+Axes (comma separated values for each):
+  --msaa <levels>              Antialiasing levels, e.g. 1,4
+  --profile <profiles>         Rendering profiles, e.g. cpu,gpu
+  --rows <counts>              Building grid sizes, e.g. 10,25
+  --builder-threads <counts>   Builder thread counts, e.g. 1,2,4
+  --sharing <modes>            Sharing modes, e.g. none,materials,all
+  --upload-order <orders>      Streaming upload orders, e.g. row,interest
+  --tessellation <counts>      Sides of round shapes, e.g. 0,8,32
+  --furniture <densities>      Interior furniture densities, e.g. 0,0.5,1
+  --scatter <densities>        Tree and street furniture densities, e.g. 0,1,2
+  --modify <fractions>         Fractions of live objects changed per second, e.g. 0,0.01,0.1
+  --animate <counts>           Objects moved every frame, e.g. 0,100,1000
+  --animate-thread <threads>   Which thread moves them, e.g. render,worker
+  --lights <counts>            Most point lights at once, e.g. 0,100,all
+  --glass <modes>              Glass in windows and doors, e.g. none,blend,cutout
+  --material-variants <counts> Distinct surfaces per texture, e.g. 1,16,256
+  --projection <kinds>         Camera projections, e.g. perspective,top-down
+  --fov <degrees>              Fields of view, e.g. 40,60,90
+  --views <counts>             Split-screen views, e.g. 1,2,4
+  --render-scale <scales>      Fractions of window resolution rendered, e.g. 0.5,1
+
+Options:
+  --help                       This menu.
+  --duration <secs>            Length of each run. Default 60.
+  --dir <dir>                  Directory for results of each run. Default sweep-results.
+  --table <file>               Write the table, as CSV, to this file. Default sweep.csv.
+";
+
+/// Sweep axes, as command line option names. Each becomes an option for each run.
+const AXES: [&str; 19] = [
+    "--msaa",
+    "--profile",
+    "--rows",
+    "--builder-threads",
+    "--sharing",
+    "--upload-order",
+    "--tessellation",
+    "--furniture",
+    "--scatter",
+    "--modify",
+    "--animate",
+    "--animate-thread",
+    "--lights",
+    "--glass",
+    "--material-variants",
+    "--projection",
+    "--fov",
+    "--views",
+    "--render-scale",
+];
+
+/// The sweep subcommand. Does not return.
+pub fn sweep() {
+    //  Options after "--" go to every run, unchanged.
+    let all_args: Vec<OsString> = std::env::args_os().skip(2).collect(); // skip "sweep"
+    let split = all_args.iter().position(|arg| arg == "--");
+    let (sweep_args, run_args) = match split {
+        Some(pos) => (all_args[..pos].to_vec(), all_args[pos + 1..].to_vec()),
+        None => (all_args, Vec::new()),
+    };
+    let mut args = Arguments::from_vec(sweep_args);
+    if args.contains(["-h", "--help"]) {
+        eprintln!("{}", HELP);
+        std::process::exit(1);
+    }
+    let mut axes = Vec::new(); // (option, values)
+    for axis in AXES {
+        if let Some(values) = option_arg::<String>(args.opt_value_from_str(axis)) {
+            let values: Vec<String> = values.split(',').map(|v| v.trim().to_string()).collect();
+            axes.push((axis, values));
+        }
+    }
+    let duration: f32 = option_arg(args.opt_value_from_str("--duration")).unwrap_or(60.0);
+    let dir: String =
+        option_arg(args.opt_value_from_str("--dir")).unwrap_or_else(|| "sweep-results".to_string());
+    let table: String =
+        option_arg(args.opt_value_from_str("--table")).unwrap_or_else(|| "sweep.csv".to_string());
+    let remaining = args.finish();
+    if !remaining.is_empty() {
+        eprint!("Unknown arguments:");
+        for flag in remaining {
+            eprint!(" '{}'", flag.to_string_lossy());
+        }
+        eprintln!("\n\n{}", HELP);
+        std::process::exit(1);
+    }
+    if let Err(e) = run_sweep(&axes, duration, &dir, &table, &run_args) {
+        eprintln!("{:?}", e);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
+/// Get option, or exit on a bad one.
+fn option_arg<T>(result: Result<Option<T>, pico_args::Error>) -> Option<T> {
+    match result {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, HELP);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// All combinations of the axis values. Each combination is a list of (option, value).
+fn combinations<'a>(axes: &[(&'a str, Vec<String>)]) -> Vec<Vec<(&'a str, String)>> {
+    let mut combos = vec![Vec::new()];
+    for (option, values) in axes {
+        combos = combos
+            .iter()
+            .flat_map(|combo| {
+                values.iter().map(move |value| {
+                    let mut combo = combo.clone();
+                    combo.push((*option, value.clone()));
+                    combo
+                })
+            })
+            .collect();
+    }
+    combos
+}
+
+/// Run all the combinations, then print and save the table.
+fn run_sweep(
+    axes: &[(&str, Vec<String>)],
+    duration: f32,
+    dir: &str,
+    table_file: &str,
+    run_args: &[OsString],
+) -> Result<(), Error> {
+    let exe = std::env::current_exe().context("Can't find render-bench executable")?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Results directory {}", dir))?;
+    let combos = combinations(axes);
+    let mut rows = Vec::new(); // (settings, results)
+    for (n, combo) in combos.iter().enumerate() {
+        let settings = combo
+            .iter()
+            .map(|(option, value)| format!("{} {}", option, value))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let settings = if settings.is_empty() {
+            "defaults".to_string()
+        } else {
+            settings
+        };
+        println!("Sweep run {} of {}: {}", n + 1, combos.len(), settings);
+        let results_file = format!("{}/run-{:03}.txt", dir, n);
+        let hitch_file = format!("{}/run-{:03}-hitches.csv", dir, n);
+        let mut command = Command::new(&exe);
+        for (option, value) in combo {
+            command.arg(option).arg(value);
+        }
+        command
+            .arg("--flythrough")
+            .arg("--duration")
+            .arg(duration.to_string())
+            .arg("--results")
+            .arg(&results_file)
+            .arg("--hitch-log")
+            .arg(&hitch_file)
+            .args(run_args);
+        let status = command
+            .status()
+            .with_context(|| format!("Running {}", exe.display()))?;
+        if !status.success() {
+            return Err(anyhow!("Sweep run {} ({}) failed: {}", n + 1, settings, status));
+        }
+        rows.push((settings, BenchResults::load(&results_file)?));
+    }
+    //  Tabulate
+    let mut csv = File::create(table_file).with_context(|| format!("Table file {}", table_file))?;
+    writeln!(
+        csv,
+        "settings,frames,all_mean_ms,all_p99_ms,mutating_mean_ms,mutating_p95_ms,mutating_p99_ms,mutating_max_ms,idle_mean_ms,idle_p99_ms"
+    )?;
+    println!();
+    println!(
+        "{:<60} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "settings", "frames", "all mean", "all p99", "mut mean", "mut p95", "mut p99", "mut max",
+        "idle mean", "idle p99"
+    );
+    for (settings, results) in &rows {
+        let all = results.summary(PhaseGroup::All);
+        let mutating = results.summary(PhaseGroup::Mutating);
+        let idle = results.summary(PhaseGroup::Idle);
+        println!(
+            "{:<60} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            settings,
+            all.count,
+            all.mean,
+            all.p99,
+            mutating.mean,
+            mutating.p95,
+            mutating.p99,
+            mutating.max,
+            idle.mean,
+            idle.p99
+        );
+        writeln!(
+            csv,
+            "\"{}\",{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3}",
+            settings,
+            all.count,
+            all.mean,
+            all.p99,
+            mutating.mean,
+            mutating.p95,
+            mutating.p99,
+            mutating.max,
+            idle.mean,
+            idle.p99
+        )?;
+    }
+    println!("Frame times in milliseconds. Table saved to {}", table_file);
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//  terrain.rs -- heightfield terrain.
+//
+//  Part of render-bench.
+//
+//  Rolling ground from seeded value noise, instead of one flat box.
+//  The ground is divided into square tiles, each its own mesh and object,
+//  the way SL divides a region's terrain into patches. Tiles can be edited
+//  while running. An edit re-uploads the whole tile, as a terrain edit in
+//  SL re-uploads a patch, so that mutation pattern is in the benchmark.
+//  Edits fade out around building footprints, so the foundations, fitted to
+//  the ground as generated, still meet the ground after any number of edits.
+//
+use super::random::Rng;
+use super::solids::{self, SceneObject, TextureSet};
+use super::stats::{self, Op};
+use core::f32::consts::PI;
+use glam::{Vec2, Vec3};
+use rend3::Renderer;
+use std::sync::Arc;
+
+/// Size of a terrain tile, meters.
+const TILE_SIZE: f32 = 32.0;
+/// Quads along each side of a tile.
+const TILE_RESOLUTION: usize = 32;
+/// Wavelength of the largest hills, meters.
+const BASE_WAVELENGTH: f32 = 96.0;
+/// Noise octaves. Each is half the wavelength and half the height of the one before.
+const OCTAVES: u32 = 3;
+/// One edit raises or lowers the middle of a tile by up to this much, meters.
+const EDIT_STEP: f32 = 0.5;
+/// Edits stop accumulating at this height, up or down, meters.
+const EDIT_LIMIT: f32 = 3.0;
+/// Edits fade in over this distance from a building footprint, meters.
+const EDIT_MARGIN: f32 = 4.0;
+
+/// The shape of the ground. Same everywhere for the same seed.
+#[derive(Debug, Copy, Clone)]
+pub struct Terrain {
+    pub seed: u64,   // for the noise
+    pub height: f32, // hills are up to this high, and valleys this deep. 0 for flat.
+}
+
+impl Terrain {
+    /// Flat ground?
+    pub fn is_flat(&self) -> bool {
+        self.height <= 0.0
+    }
+
+    /// Ground height at a point.
+    pub fn height_at(&self, p: Vec2) -> f32 {
+        if self.is_flat() {
+            return 0.0;
+        }
+        let mut sum = 0.0;
+        let mut total_weight = 0.0;
+        for octave in 0..OCTAVES {
+            let weight = 0.5_f32.powi(octave as i32);
+            let wavelength = BASE_WAVELENGTH * weight;
+            sum += weight * self.noise(p / wavelength, octave);
+            total_weight += weight;
+        }
+        self.height * sum / total_weight
+    }
+
+    /// Lowest and highest ground in a rectangle, sampled at least every meter.
+    pub fn range(&self, low: Vec2, high: Vec2) -> (f32, f32) {
+        let steps = (high - low).ceil().max(Vec2::ONE);
+        let mut range = (f32::MAX, f32::MIN);
+        for i in 0..=steps.x as usize {
+            for j in 0..=steps.y as usize {
+                let fraction = Vec2::new(i as f32, j as f32) / steps;
+                let height = self.height_at(low + (high - low) * fraction);
+                range = (range.0.min(height), range.1.max(height));
+            }
+        }
+        range
+    }
+
+    /// Smooth value noise, -1 to 1, with lattice points one unit apart.
+    fn noise(&self, p: Vec2, octave: u32) -> f32 {
+        let cell = p.floor();
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let f = p - cell;
+        let (fx, fz) = (smooth(f.x), smooth(f.y));
+        let corner =
+            |dx: i32, dz: i32| self.lattice(cell.x as i32 + dx, cell.y as i32 + dz, octave);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        lerp(
+            lerp(corner(0, 0), corner(1, 0), fx),
+            lerp(corner(0, 1), corner(1, 1), fx),
+            fz,
+        )
+    }
+
+    /// Random value at a lattice point, -1 to 1.
+    fn lattice(&self, i: i32, j: i32, octave: u32) -> f32 {
+        let key = ((i as u32 as u64) << 32) | j as u32 as u64;
+        let mut rng = Rng::new(self.seed.wrapping_add(octave as u64) ^ key);
+        rng.range(-1.0, 1.0)
+    }
+}
+
+/// One tile of terrain, and the object showing it.
+pub struct TerrainTile {
+    corner: Vec2,                  // low corner, in X and Z
+    edit: f32,                     // height added at the middle by edits, 0 at the edges
+    footprints: Vec<(Vec2, Vec2)>, // building footprints near this tile, low and high corners
+    object: SceneObject,           // keeps the tile alive
+}
+
+impl TerrainTile {
+    /// Height of this tile, with edits, at a point in world X and Z.
+    fn height_at(&self, terrain: &Terrain, p: Vec2) -> f32 {
+        let local = ((p - self.corner) / TILE_SIZE).clamp(Vec2::ZERO, Vec2::ONE);
+        let taper = (local.x * PI).sin() * (local.y * PI).sin();
+        terrain.height_at(p) + self.edit * taper * self.editable(p)
+    }
+
+    /// How much of an edit applies at a point. None on a footprint, all at EDIT_MARGIN from every one.
+    fn editable(&self, p: Vec2) -> f32 {
+        self.footprints
+            .iter()
+            .map(|(low, high)| {
+                let outside = (*low - p).max(p - *high).max(Vec2::ZERO); // distance outside, by axis
+                (outside.length() / EDIT_MARGIN).min(1.0)
+            })
+            .fold(1.0, f32::min)
+    }
+
+    /// Make the object for this tile, as edited.
+    fn draw(
+        &self,
+        renderer: &Arc<Renderer>,
+        terrain: &Terrain,
+        texture_set: &TextureSet,
+    ) -> SceneObject {
+        solids::create_heightfield(
+            renderer,
+            (TILE_SIZE, TILE_RESOLUTION),
+            &|p| self.height_at(terrain, p),
+            Vec3::new(self.corner.x, 0.0, self.corner.y),
+            texture_set,
+        )
+    }
+}
+
+/// Draw terrain covering a square, centered on the origin.
+//  Footprints are where buildings stand, as low and high corners in X and Z.
+//  Later edits leave the ground under them alone.
+pub fn draw_terrain(
+    renderer: &Arc<Renderer>,
+    terrain: &Terrain,
+    world_size: f32,
+    texture_set: &TextureSet,
+    footprints: &[(Vec2, Vec2)],
+) -> Vec<TerrainTile> {
+    profiling::scope!("Draw terrain");
+    let tiles_per_side = (world_size / TILE_SIZE).ceil() as usize;
+    let start = -(tiles_per_side as f32) * TILE_SIZE * 0.5;
+    let mut tiles = Vec::with_capacity(tiles_per_side * tiles_per_side);
+    for i in 0..tiles_per_side {
+        for j in 0..tiles_per_side {
+            let corner = Vec2::new(start + i as f32 * TILE_SIZE, start + j as f32 * TILE_SIZE);
+            let object = solids::create_heightfield(
+                renderer,
+                (TILE_SIZE, TILE_RESOLUTION),
+                &|p| terrain.height_at(p), // not edited yet
+                Vec3::new(corner.x, 0.0, corner.y),
+                texture_set,
+            );
+            let near = |(low, high): &&(Vec2, Vec2)| {
+                low.cmplt(corner + Vec2::splat(TILE_SIZE + EDIT_MARGIN))
+                    .all()
+                    && high.cmpgt(corner - Vec2::splat(EDIT_MARGIN)).all()
+            };
+            tiles.push(TerrainTile {
+                corner,
+                edit: 0.0,
+                footprints: footprints.iter().filter(near).copied().collect(),
+                object,
+            });
+        }
+    }
+    tiles
+}
+
+/// Raise or lower one tile, chosen at random, and upload it again.
+//  The new tile is added before the old one is dropped, so there is no gap.
+pub fn edit_random_tile(
+    renderer: &Arc<Renderer>,
+    terrain: &Terrain,
+    tiles: &mut [TerrainTile],
+    texture_set: &TextureSet,
+    rng: &mut Rng,
+) {
+    if tiles.is_empty() {
+        return;
+    }
+    profiling::scope!("Edit terrain tile");
+    let tile = &mut tiles[rng.below(tiles.len())];
+    tile.edit = (tile.edit + rng.range(-EDIT_STEP, EDIT_STEP)).clamp(-EDIT_LIMIT, EDIT_LIMIT);
+    let object = tile.draw(renderer, terrain, texture_set);
+    let old = std::mem::replace(&mut tile.object, object);
+    stats::timed(Op::DropObject, || drop(old));
+}
@@ -5,21 +5,20 @@
 //
 //  Shared memory threaded targets only - no Android.
 //
-use anyhow::{anyhow, Context, Error};
-use glam::{DVec2, Mat3A, Mat4, UVec2, Vec3, Vec3A};
+use glam::{DVec2, Mat3A, UVec2, Vec3, Vec3A};
 use pico_args::Arguments;
 use rend3::{
     types::{
-        Backend, Camera, CameraProjection, DirectionalLight, DirectionalLightHandle, SampleCount,
-        Texture, TextureFormat,
+        Backend, Camera, DirectionalLight, DirectionalLightChange,
+        DirectionalLightHandle, SampleCount, TextureCubeHandle,
     },
     util::typedefs::FastHashMap,
     Renderer, RendererProfile,
 };
-use rend3_framework::{lock, Mutex};
+use rend3_framework::lock;
 use rend3_routine::{skybox::SkyboxRoutine};
 use std::time::Instant;
-use std::{collections::HashMap, hash::BuildHasher, path::Path, sync::Arc, time::Duration};
+use std::{collections::HashMap, hash::BuildHasher, sync::Arc, time::Duration};
 use wgpu_profiler::GpuTimerScopeResult;
 use winit::{
     event::{DeviceEvent, ElementState, Event, MouseButton, WindowEvent, KeyEvent},
@@ -27,14 +26,25 @@ use winit::{
     keyboard::{KeyCode},
 };
 
+use super::animation::AnimationThread;
+use super::camera::{self, CameraSettings, Projection};
 use super::citybuilder::{CityBuilder, CityParams};
+use super::daylight::{DayCycle, Sky};
+use super::interest::UploadOrder;
+use super::lights;
+use super::resolution::{parse_resolution, RenderScale};
+use super::results::BenchResults;
+use super::skybox::{CubeImage, SkyboxSource};
+use super::solids::{self, Sharing};
+use super::stats::{self, GpuTimingStats, HitchDetector, Op, ViewTimingStats};
+use super::views::{self, Pose, Split};
 //
 //  Constants
 //
 //  Names of all the assets files.
 const SKYBOX_TEXTURES_DIR: &str = "/resources/skybox";
 const CITY_TEXTURES_DIR: &str = "/resources/city";
-const CITY_TEXTURES: [(&str, &str, &str, f32); 6] = [
+const CITY_TEXTURES: [(&str, &str, &str, f32); 8] = [
     ("brick", "redbrick_albedo.png", "redbrick_normal.png", 0.25),
     (
         "ground",
@@ -66,66 +76,21 @@ const CITY_TEXTURES: [(&str, &str, &str, f32); 6] = [
         "white_stone_normal.png",
         0.25,
     ),
+    (
+        "marble",
+        "white_marble_albedo.png",
+        "white_marble_normal.png",
+        0.5,
+    ),
+    (
+        "green_marble",
+        "green_marble_albedo.png",
+        "green_marble_normal.png",
+        0.5,
+    ),
 ];
-
-/// Load all faces of a skybox image. Output bytes as one big RGBA-ordered image.
-fn load_skybox_images(prefix: &str, filenames: &[&str]) -> Result<((u32, u32), Vec<u8>), Error> {
-    println!("Loading skybox textures.");
-    use image::{EncodableLayout, GenericImageView};
-    let mut v = Vec::new(); // accum bytes
-    let mut dims: Option<(u32, u32)> = None; // size of objects
-    if filenames.len() != 6 {
-        return Err(anyhow!("Skybox image set must have exactly 6 images"));
-    }
-    for filename in filenames {
-        let full_pathname = format!("{}/{}", prefix, filename);
-        let img = image::open(&full_pathname)
-            .with_context(|| format!("Skybox file {}", full_pathname))?;
-        //  Check that all images have the same dimensions
-        match dims {
-            Some(dims) => {
-                if img.dimensions() != dims {
-                    return Err(anyhow!(
-                        "Skybox image {} is {:?} but others are {:?}",
-                        filename,
-                        img.dimensions(),
-                        dims
-                    ));
-                }
-            }
-            None => {
-                dims = Some(img.dimensions());
-            }
-        }
-        v.extend_from_slice(img.to_rgba8().as_bytes()); // load image
-    }
-    Ok((dims.unwrap(), v))
-}
-
-/// Load the skybox from individual images.
-fn load_skybox(renderer: &Arc<Renderer>, skybox_routine: &Mutex<SkyboxRoutine>) -> Result<(), Error> {
-    let prefix = env!("CARGO_MANIFEST_DIR").to_owned() + SKYBOX_TEXTURES_DIR; // filename prefix
-    let skybox_files: [&str; 6] = [
-        "right.jpg",
-        "left.jpg",
-        "top.jpg",
-        "bottom.jpg",
-        "front.jpg",
-        "back.jpg",
-    ];
-    let (dims, image) = load_skybox_images(&prefix, &skybox_files)?; // Combine into one big texture
-    let handle = renderer.add_texture_cube(Texture {
-        format: TextureFormat::Rgba8UnormSrgb,
-        size: UVec2::new(dims.0, dims.1),
-        data: image,
-        label: Some("background".into()),
-        mip_count: rend3::types::MipmapCount::ONE,
-        mip_source: rend3::types::MipmapSource::Uploaded,
-    }).expect("Error adding texture cube");
-    //  Finally set skybox
-    skybox_routine.lock().set_background_texture(Some(handle));
-    Ok(())
-}
+/// Longest time, in seconds, an option can ask for. A year.
+const MAX_SECS: f32 = 365.0 * 24.0 * 60.0 * 60.0;
 
 fn button_pressed<Hash: BuildHasher>(map: &HashMap<KeyCode, bool, Hash>, key: KeyCode) -> bool {
     map.get(&key).map_or(false, |b| *b)
@@ -163,17 +128,30 @@ fn extract_vec3(value: &str) -> Result<Vec3, &'static str> {
     let split: Vec<_> = value.split(',').enumerate().collect();
 
     if split.len() != 3 {
-        return Err("Directional lights are defined with 3 values");
+        return Err("Directions and positions are defined with 3 values");
     }
 
     for (idx, inner) in split {
         let inner = inner.trim();
 
-        res[idx] = inner.parse().map_err(|_| "Cannot parse direction number")?;
+        res[idx] = inner.parse().map_err(|_| "Cannot parse direction or position number")?;
     }
     Ok(Vec3::from(res))
 }
 
+fn extract_lod_distances(value: &str) -> Result<(f32, f32), &'static str> {
+    let split: Vec<_> = value.split(',').map(|v| v.trim()).collect();
+    if split.len() != 2 {
+        return Err("LOD distances are defined with 2 values");
+    }
+    let near: f32 = split[0].parse().map_err(|_| "Cannot parse LOD distance")?;
+    let far: f32 = split[1].parse().map_err(|_| "Cannot parse LOD distance")?;
+    if near > far {
+        return Err("Near LOD distance must not be beyond far LOD distance");
+    }
+    Ok((near, far))
+}
+
 fn option_arg<T>(result: Result<Option<T>, pico_args::Error>) -> Option<T> {
     match result {
         Ok(o) => o,
@@ -198,9 +176,13 @@ render-bench
 Exercise Rend3 and WGPU with a complex, changing scene.
 
 usage: render-bench --options 
+       render-bench compare <baseline results> <new results> --options
+       render-bench sweep --axes --options [-- options for every run]
 
 Meta:
   --help            This menu.
+  compare           Compare two results files saved with --results. 'render-bench compare --help' for options.
+  sweep             Run once for each combination of settings, and tabulate. 'render-bench sweep --help' for options.
 
 Rendering:
   -b --backend                 Choose backend to run on ('vk', 'dx12', 'dx11', 'metal', 'gl').
@@ -211,6 +193,9 @@ Rendering:
 Windowing:
   --absolute-mouse             Interpret the relative mouse coordinates as absolute. Useful when using things like VNC.
   --fullscreen                 Open the window in borderless fullscreen.
+  --resolution <WxH>           Open the window at this size, as 1920x1080, instead of maximized.
+  --render-scale <scale>       Render at this fraction of the window's resolution, 0.25 to 2. Default 1.
+  --dynamic-resolution <ms>    Experimental. Lower the render scale when frames take longer than this, raise it when well under.
 
 Assets:
   --normal-y-down                        Interpret all normals as having the DirectX convention of Y down. Defaults to Y up.
@@ -219,10 +204,52 @@ Assets:
   --ambient <value>                      Set the value of the minimum ambient light. This will be treated as white light of this intensity. Defaults to 0.1.
   --scale <scale>                        Scale all objects loaded by this factor. Defaults to 1.0.
   --shadow-distance <value>              Distance from the camera there will be directional shadows. Lower values means higher quality shadows. Defaults to 300.
+  --day-length <secs>                    Day and night cycle, one day in this many seconds. The sun moves, instead of pointing as --directional-light says.
+  --skybox <dir>                         Skybox from six images in dir, named right, left, top, bottom, front and back. 'procedural' for a generated gradient, 'none' for no skybox. Defaults to the included sky.
+  --skybox-swap <secs>                   Upload the skybox again this often, as a new cube texture, to measure cube texture uploads.
+
+City:
+  --rows <n>                   Buildings are in a square grid with this many rows. Half are permanent. Default 25.
+  --builder-threads <n>        Number of threads adding and deleting buildings. Default 1.
+  --sharing <mode>             What blocks share: 'none', 'materials', or 'all' (meshes and materials). Default none.
+  --lod <near,far>             Buildings beyond near distance are drawn as shells, beyond far as boxes. Default all full detail.
+  --tessellation <n>           Round columns, domes and railings, with this many sides per circle, 3 or more. Default 0, all square.
+  --furniture <density>        Furnish building interiors. Density is the fraction of floor space used, 0 to 1. Default 0.
+  --scatter <density>          Trees, lamp posts, benches and signs around buildings. 1 is about one per 4 meters. Default 0.
+  --terrain <height>           Rolling terrain, with hills and valleys up to this many meters. Not with --streets or --stream. Default 0, flat.
+  --terrain-edits <n>          Edit and re-upload this many terrain tiles per second, on a thread of their own. Default 0.
+  --modify <fraction>          Change this fraction of live objects per second: transform, material, or mesh. Default 0.
+  --animate <n>                Vehicles driving along the streets and turning signs, this many, moved every frame. Default 0.
+  --animate-thread <thread>    Move animated objects from the 'render' thread or a 'worker' thread. Default worker.
+  --lights <n>                 Point lights in lit windows and street lamps, at most n at once, or 'all'. Default 0.
+  --glass <mode>               Glass in windows and doors: 'none', 'blend' (translucent), or 'cutout' (alpha tested). Default none.
+  --material-variants <n>      Distinct surfaces per texture: tint, metallic, roughness, glow, texture placement. Default 1, all plain.
+  --material-variety <amount>  How far material variants stray from plain, 0 to 1. Default 0.5.
+  --aomr                       Ambient occlusion, metallic and roughness maps with every texture, shipped or generated.
+  --seed <n>                   Seed for furniture and other random content. Default 1.
+  --streets                    Streets, sidewalks and intersections between buildings. Spreads the city out.
+  --stream <radius>            Load buildings within this many meters of the camera, drop those beyond. Unbounded city.
+  --upload-order <order>       When streaming, upload buildings in row order or by interest (distance and screen size). Default interest.
 
 Controls:
+  --flythrough                 Camera circles the city on a fixed path, for repeatable runs.
+  --projection <kind>          'perspective', or 'top-down' for an orthographic view of the whole city from above. Default perspective.
+  --fov <degrees>              Vertical field of view. Default 60.
+  --near <meters>              Near clipping plane distance. Default 0.1.
+  --camera-pos <x,y,z>         Starting camera position. Default 3,2,3.
+  --camera-angles <yaw,pitch>  Starting camera heading and pitch, in degrees. Default 45,-22.5.
+  --views <n>                  Split the window into 1, 2 or 4 views, each with its own camera. Default 1.
+  --monitor                    Security camera monitor: a second full scene pass every frame, from a panning camera, shown inset.
   --walk <speed>               Walk speed (speed without holding shift) in units/second (typically meters). Default 10.
   --run  <speed>               Run speed (speed while holding shift) in units/second (typically meters). Default 50.
+
+Statistics:
+  --hitch-factor <value>       A frame taking more than this times the median of recent frames is a hitch. Default 2.0.
+  --hitch-log <file>           File to which hitches are logged, as CSV. Default hitches.csv.
+  --gpu-trace-frames <n>       Frames in a GPU timing chrome trace, started with the P key. Default 100.
+  --gpu-trace-after <secs>     Also start a GPU timing chrome trace this many seconds after startup.
+  --results <file>             Save every frame time, with what the city builder was doing, to this file on exit.
+  --duration <secs>            Exit after this many seconds.
 ";
 
 struct SceneViewer {
@@ -237,18 +264,38 @@ struct SceneViewer {
     directional_light_intensity: f32,
     directional_light: Option<DirectionalLightHandle>,
     ambient_light_level: f32,
+    day_cycle: Option<DayCycle>,              // if the sun moves
+    skybox_image: Option<CubeImage>,          // skybox faces, if any, kept for swaps
+    skybox: Option<TextureCubeHandle>,        // background, once loaded
+    skybox_shown: bool,                       // background is being drawn
+    skybox_swap: Option<(Duration, Instant)>, // how often to upload the skybox again, and when last done
     samples: SampleCount,
 
     fullscreen: bool,
+    resolution: Option<UVec2>,                // window size, if not maximized
+    render_scale: RenderScale,                // fraction of window resolution rendered
+    shown_resolution: Option<(UVec2, UVec2)>, // window and render resolution, as last printed
 
     scancode_status: FastHashMap<KeyCode, bool>,
     camera_pitch: f32,
     camera_yaw: f32,
     camera_location: Vec3A,
+    camera_settings: CameraSettings, // projection and starting pose
+    split: Split,                    // views the window is split into
+    monitor: bool,                   // security camera monitor inset
+    flythrough: bool,
     previous_profiling_stats: Option<Vec<GpuTimerScopeResult>>,
+    timestamp_start: Instant,
     timestamp_last_second: Instant,
     timestamp_last_frame: Instant,
     frame_times: histogram::Histogram,
+    hitch_detector: HitchDetector,
+    gpu_stats: GpuTimingStats,
+    view_stats: ViewTimingStats,   // CPU time per view, if split
+    gpu_trace_at: Option<Instant>, // when to start automatic GPU trace
+    gpu_trace_key_down: bool,      // P key was down last frame
+    results: Option<(String, BenchResults)>, // results file name, and results so far
+    exit_at: Option<Instant>,                // when to exit, if timed run
     last_mouse_delta: Option<DVec2>,
 
     grabber: Option<rend3_framework::Grabber>,
@@ -276,6 +323,14 @@ impl SceneViewer {
         // Windowing
         let absolute_mouse: bool = args.contains("--absolute-mouse");
         let fullscreen = args.contains("--fullscreen");
+        let resolution = option_arg(args.opt_value_from_fn("--resolution", parse_resolution));
+        //  Android has very low memory bandwidth, so lets run internal buffers at half
+        //  res by default
+        let default_render_scale = if cfg!(target_os = "android") { 0.5 } else { 1.0 };
+        let render_scale: f32 =
+            option_arg(args.opt_value_from_str("--render-scale")).unwrap_or(default_render_scale);
+        let frame_time_target: Option<f32> =
+            option_arg(args.opt_value_from_str("--dynamic-resolution"));
 
         // Assets
         let directional_light_direction =
@@ -287,11 +342,86 @@ impl SceneViewer {
             option_arg(args.opt_value_from_str("--directional-light-intensity")).unwrap_or(4.0);
         let ambient_light_level: f32 =
             option_arg(args.opt_value_from_str("--ambient")).unwrap_or(0.10);
+        let day_length: Option<f32> = option_arg(args.opt_value_from_str("--day-length"));
+        let skybox_source = option_arg(args.opt_value_from_fn("--skybox", SkyboxSource::parse))
+            .unwrap_or_else(|| {
+                SkyboxSource::Files(env!("CARGO_MANIFEST_DIR").to_owned() + SKYBOX_TEXTURES_DIR)
+            });
+        let skybox_swap: Option<f32> = option_arg(args.opt_value_from_str("--skybox-swap"));
+
+        // City
+        let bldg_rows: usize = option_arg(args.opt_value_from_str("--rows")).unwrap_or(25);
+        let builder_threads: usize =
+            option_arg(args.opt_value_from_str("--builder-threads")).unwrap_or(1);
+        let sharing = option_arg(args.opt_value_from_fn("--sharing", Sharing::parse))
+            .unwrap_or(Sharing::None);
+        let lod_distances = option_arg(args.opt_value_from_fn("--lod", extract_lod_distances));
+        let tessellation: u32 =
+            option_arg(args.opt_value_from_fn("--tessellation", solids::parse_tessellation))
+                .unwrap_or(0);
+        let furniture_density: f32 =
+            option_arg(args.opt_value_from_str("--furniture")).unwrap_or(0.0);
+        let scatter_density: f32 = option_arg(args.opt_value_from_str("--scatter")).unwrap_or(0.0);
+        let terrain_height: f32 = option_arg(args.opt_value_from_str("--terrain")).unwrap_or(0.0);
+        let terrain_edits: f32 =
+            option_arg(args.opt_value_from_str("--terrain-edits")).unwrap_or(0.0);
+        let modify_rate: f32 = option_arg(args.opt_value_from_str("--modify")).unwrap_or(0.0);
+        let animated: usize = option_arg(args.opt_value_from_str("--animate")).unwrap_or(0);
+        let animation_thread =
+            option_arg(args.opt_value_from_fn("--animate-thread", AnimationThread::parse))
+                .unwrap_or(AnimationThread::Worker);
+        let max_lights = option_arg(args.opt_value_from_fn("--lights", lights::parse_light_limit))
+            .unwrap_or(0);
+        let glass = option_arg(args.opt_value_from_fn("--glass", solids::parse_glass)).flatten();
+        let material_variants: usize =
+            option_arg(args.opt_value_from_str("--material-variants")).unwrap_or(1);
+        let material_variety: f32 =
+            option_arg(args.opt_value_from_str("--material-variety")).unwrap_or(0.5);
+        let aomr = args.contains("--aomr");
+        let seed: u64 = option_arg(args.opt_value_from_str("--seed")).unwrap_or(1);
+        let streets = args.contains("--streets");
+        let stream_radius: Option<f32> = option_arg(args.opt_value_from_str("--stream"));
+        let upload_order = option_arg(args.opt_value_from_fn("--upload-order", UploadOrder::parse))
+            .unwrap_or(UploadOrder::Interest);
 
         // Controls
+        let flythrough = args.contains("--flythrough");
+        let default_camera = CameraSettings::default();
+        let projection = option_arg(args.opt_value_from_fn("--projection", Projection::parse))
+            .unwrap_or(default_camera.projection);
+        let vfov: f32 = option_arg(args.opt_value_from_str("--fov")).unwrap_or(default_camera.vfov);
+        let near: f32 =
+            option_arg(args.opt_value_from_str("--near")).unwrap_or(default_camera.near);
+        let camera_position = option_arg(args.opt_value_from_fn("--camera-pos", extract_vec3))
+            .unwrap_or(default_camera.position);
+        let (camera_yaw, camera_pitch) =
+            option_arg(args.opt_value_from_fn("--camera-angles", camera::parse_camera_angles))
+                .unwrap_or((default_camera.yaw, default_camera.pitch));
+        let camera_settings = CameraSettings {
+            projection,
+            vfov: vfov.clamp(1.0, 179.0),
+            near: near.max(0.001),
+            position: camera_position,
+            yaw: camera_yaw,
+            pitch: camera_pitch,
+        };
+        let (camera_location, camera_yaw, camera_pitch) = camera_settings.start_pose();
+        let split =
+            option_arg(args.opt_value_from_fn("--views", Split::parse)).unwrap_or(Split::One);
+        let monitor = args.contains("--monitor");
         let walk_speed = args.value_from_str("--walk").unwrap_or(10.0_f32);
         let run_speed = args.value_from_str("--run").unwrap_or(50.0_f32);
 
+        // Statistics
+        let hitch_factor: f32 = option_arg(args.opt_value_from_str("--hitch-factor")).unwrap_or(2.0);
+        let hitch_log: String = option_arg(args.opt_value_from_str("--hitch-log"))
+            .unwrap_or_else(|| "hitches.csv".to_string());
+        let gpu_trace_frames: usize =
+            option_arg(args.opt_value_from_str("--gpu-trace-frames")).unwrap_or(100);
+        let gpu_trace_after: Option<f32> = option_arg(args.opt_value_from_str("--gpu-trace-after"));
+        let results_file: Option<String> = option_arg(args.opt_value_from_str("--results"));
+        let duration: Option<f32> = option_arg(args.opt_value_from_str("--duration"));
+
         // Free args
         let remaining = args.finish();
 
@@ -311,12 +441,70 @@ impl SceneViewer {
             eprintln!("{}", HELP);
             std::process::exit(1);
         }
+        if terrain_height > 0.0 && (streets || stream_radius.is_some()) {
+            eprintln!("--terrain cannot be used with --streets or --stream, which need flat ground.");
+            std::process::exit(1);
+        }
+        if gpu_trace_frames == 0 {
+            eprintln!("--gpu-trace-frames must be at least 1.");
+            std::process::exit(1);
+        }
+        if gpu_trace_after.is_some_and(|secs| !(0.0..=MAX_SECS).contains(&secs)) {
+            eprintln!("--gpu-trace-after must be from 0 to {} seconds.", MAX_SECS);
+            std::process::exit(1);
+        }
+        if duration.is_some_and(|secs| !(0.0..=MAX_SECS).contains(&secs)) {
+            eprintln!("--duration must be from 0 to {} seconds.", MAX_SECS);
+            std::process::exit(1);
+        }
+        if skybox_swap.is_some_and(|secs| !(f32::MIN_POSITIVE..=MAX_SECS).contains(&secs)) {
+            eprintln!("--skybox-swap must be over 0 and at most {} seconds.", MAX_SECS);
+            std::process::exit(1);
+        }
+
+        let hitch_detector = match HitchDetector::new(hitch_factor, &hitch_log) {
+            Ok(detector) => detector,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+        };
 
         //  Parameters for city building
-        let city_params = CityParams::new(
+        let mut city_params = CityParams::new(
             env!("CARGO_MANIFEST_DIR").to_owned() + CITY_TEXTURES_DIR,
             CITY_TEXTURES.to_vec(),
         );
+        city_params.bldg_rows = bldg_rows;
+        city_params.builder_threads = builder_threads;
+        city_params.sharing = sharing;
+        city_params.lod_distances = lod_distances;
+        city_params.tessellation = tessellation;
+        city_params.furniture_density = furniture_density.clamp(0.0, 1.0);
+        city_params.scatter_density = scatter_density.max(0.0);
+        city_params.terrain_height = terrain_height.max(0.0);
+        city_params.terrain_edits = terrain_edits.max(0.0);
+        city_params.modify_rate = modify_rate.max(0.0);
+        city_params.animated = animated;
+        city_params.animation_thread = animation_thread;
+        city_params.max_lights = max_lights;
+        city_params.glass = glass;
+        city_params.material_variants = material_variants.max(1);
+        city_params.material_variety = material_variety.clamp(0.0, 1.0);
+        city_params.aomr = aomr;
+        city_params.seed = seed;
+        city_params.streets = streets;
+        city_params.stream_radius = stream_radius;
+        city_params.upload_order = upload_order;
+
+        //  Load the skybox images now, so bad ones are reported before the window opens.
+        let skybox_image = match CubeImage::load(&skybox_source) {
+            Ok(image) => image,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+        };
 
         Self {
             absolute_mouse,
@@ -329,18 +517,46 @@ impl SceneViewer {
             directional_light_intensity,
             directional_light: None,
             ambient_light_level,
+            day_cycle: day_length.map(|secs| {
+                DayCycle::new(secs, directional_light_intensity, ambient_light_level)
+            }),
+            skybox_image,
+            skybox: None,
+            skybox_shown: false,
+            skybox_swap: skybox_swap.map(|secs| (Duration::from_secs_f32(secs), Instant::now())),
             samples,
 
             fullscreen,
+            resolution,
+            render_scale: RenderScale::new(render_scale, frame_time_target),
+            shown_resolution: None,
 
             scancode_status: FastHashMap::default(),
-            camera_pitch: -std::f32::consts::FRAC_PI_8,
-            camera_yaw: std::f32::consts::FRAC_PI_4,
-            camera_location: Vec3A::new(3.0, 2.0, 3.0),
+            camera_pitch,
+            camera_yaw,
+            camera_location,
+            camera_settings,
+            split,
+            monitor,
+            flythrough,
             previous_profiling_stats: None,
+            timestamp_start: Instant::now(),
             timestamp_last_second: Instant::now(),
             timestamp_last_frame: Instant::now(),
             frame_times: histogram::Histogram::new(),
+            hitch_detector,
+            gpu_stats: GpuTimingStats::new(gpu_trace_frames, "profile.json"),
+            view_stats: ViewTimingStats::new(),
+            gpu_trace_at: gpu_trace_after.map(|secs| Instant::now() + Duration::from_secs_f32(secs)),
+            gpu_trace_key_down: false,
+            results: results_file.map(|filename| {
+                let results = BenchResults {
+                    comments: vec![std::env::args().collect::<Vec<String>>().join(" ")],
+                    ..Default::default()
+                };
+                (filename, results)
+            }),
+            exit_at: duration.map(|secs| Instant::now() + Duration::from_secs_f32(secs)),
             last_mouse_delta: None,
 
             grabber: None,
@@ -393,16 +609,10 @@ impl rend3_framework::App for SceneViewer {
         self.samples
     }
 
+    //  The surface is always at the window's resolution. The render scale
+    //  is applied to the render graph's targets, each frame. See resolution.rs.
     fn scale_factor(&self) -> f32 {
-        // Android has very low memory bandwidth, so lets run internal buffers at half
-        // res by default
-        cfg_if::cfg_if! {
-            if #[cfg(target_os = "android")] {
-                0.5
-            } else {
-                1.0
-            }
-        }
+        1.0
     }
 
     fn setup(&mut self, context: rend3_framework::SetupContext<'_>) {
@@ -414,6 +624,10 @@ impl rend3_framework::App for SceneViewer {
 
 
         const SUN_SHADOW_DISTANCE: f32 = 300.0;
+        //  With a day cycle, the sun starts where the cycle says.
+        if let Some(cycle) = &self.day_cycle {
+            self.directional_light_direction = Some(cycle.sky(0.0).direction);
+        }
         if let Some(direction) = self.directional_light_direction {
             self.directional_light = Some(context.renderer.add_directional_light(DirectionalLight {
                 color: Vec3::splat(1.0),
@@ -433,9 +647,13 @@ impl rend3_framework::App for SceneViewer {
         ////let _window_size = context.window.inner_size();       
         
         
-        load_skybox(&renderer, &context.routines.skybox).unwrap(); // load the background skybox
-        let thread_count = 1; // ***TEMP***
-        self.city_builder.start(thread_count, renderer); // start up the city generator
+        //  Load the background skybox, if any.
+        self.skybox = self.skybox_image.as_ref().map(|image| image.upload(&renderer));
+        context.routines.skybox.lock().set_background_texture(self.skybox.clone());
+        self.skybox_shown = true;
+        self.city_builder.start(renderer); // start up the city generator
+        //  The first frame is timed from here, not from before setup.
+        self.timestamp_last_frame = Instant::now();
     }
 
             
@@ -448,6 +666,16 @@ impl rend3_framework::App for SceneViewer {
         self.frame_times
             .increment(delta_time.as_micros() as u64)
             .unwrap();
+        let phase = self.city_builder.phase();
+        self.hitch_detector.frame(now, delta_time, phase.name());
+        if let Some((_, ref mut results)) = self.results {
+            results.push(phase.name(), delta_time.as_micros() as u64);
+        }
+        if self.exit_at.is_some_and(|at| now >= at) {
+            println!("Run duration reached.");
+            self.shutdown();
+            std::process::exit(0);
+        }
 
         let elapsed_since_second = now - self.timestamp_last_second;
         if elapsed_since_second > Duration::from_secs(1) {
@@ -459,7 +687,8 @@ impl rend3_framework::App for SceneViewer {
                 95%: {:0>5.2}ms; \
                 99%: {:0>5.2}ms; \
                 Max: {:0>5.2}ms; \
-                StdDev: {:0>5.2}ms",
+                StdDev: {:0>5.2}ms; \
+                Hitches: {}",
                 count,
                 elapsed_since_second.as_secs_f32(),
                 self.frame_times.minimum().unwrap() as f32 / 1_000.0,
@@ -468,79 +697,172 @@ impl rend3_framework::App for SceneViewer {
                 self.frame_times.percentile(99.0).unwrap() as f32 / 1_000.0,
                 self.frame_times.maximum().unwrap() as f32 / 1_000.0,
                 self.frame_times.stddev().unwrap() as f32 / 1_000.0,
+                self.hitch_detector.take_hitch_count(),
             );
+            self.gpu_stats.report();
+            self.view_stats.report();
+            //  Rend3 calls made from this thread: sky, skybox, and animation if moved from here.
+            if stats::op_stats().used() {
+                stats::op_stats().report("Render thread, last second");
+            }
+            self.render_scale
+                .adjust(self.frame_times.mean().unwrap() as f32 / 1_000.0);
             self.timestamp_last_second = now;
             self.frame_times.clear();
         }
 
         self.timestamp_last_frame = now;
+        //  Print the resolution at startup, and whenever the window or the render scale changes it.
+        let shown = (
+            context.resolution,
+            self.render_scale.internal(context.resolution),
+        );
+        if self.shown_resolution != Some(shown) {
+            println!(
+                "Window {}x{}, rendering at {}x{} (scale {:.2})",
+                shown.0.x,
+                shown.0.y,
+                shown.1.x,
+                shown.1.y,
+                self.render_scale.scale()
+            );
+            self.shown_resolution = Some(shown);
+        }
+        if self.gpu_trace_at.is_some_and(|at| now >= at) {
+            self.gpu_trace_at = None;
+            self.gpu_stats.start_trace();
+        }
 
         self.handle_button(&context, delta_time);
-
-        let view = Mat4::from_euler(
-            glam::EulerRot::XYZ,
-            -self.camera_pitch,
-            -self.camera_yaw,
-            0.0,
+        //  The top-down view stays put over the city.
+        if self.flythrough && self.camera_settings.projection != Projection::TopDown {
+            self.fly_camera(now);
+        }
+        //  Forward is (-cos(pitch) sin(yaw), sin(pitch), -cos(pitch) cos(yaw)).
+        let forward = Vec3::new(
+            -self.camera_pitch.cos() * self.camera_yaw.sin(),
+            self.camera_pitch.sin(),
+            -self.camera_pitch.cos() * self.camera_yaw.cos(),
         );
-        let view = view * Mat4::from_translation((-self.camera_location).into());
+        self.city_builder
+            .set_camera_view(self.camera_location.into(), forward);
 
-        context.renderer.set_camera_data(Camera {
-            projection: CameraProjection::Perspective {
-                vfov: 60.0,
-                near: 0.1,
-            },
-            view,
-        });
+        //  Animated objects, if moved from this thread, go in with this frame.
+        self.city_builder.animate(context.renderer);
 
         //// Get a frame
         ////let frame = context.surface.unwrap().get_current_texture().unwrap();
-        // Evaluate our frame's world-change instructions
         // Lock all the routines
         let pbr_routine = lock(&context.routines.pbr);
         let mut skybox_routine = lock(&context.routines.skybox);
         let tonemapping_routine = lock(&context.routines.tonemapping);
-        //  Swap the instruction buffers. This begins a new frame.
-        context.renderer.swap_instruction_buffers();
-
-
-        // Ready up the renderer
-        // Ready up the routines
-        let mut eval_output = context.renderer.evaluate_instructions();
-        skybox_routine.evaluate(context.renderer);
-
-        // Build a rendergraph
-        let mut graph = rend3::graph::RenderGraph::new();
-        let frame_handle = graph.add_imported_render_target(
-            context.surface_texture,
-            0..1,
-            0..1,
-            rend3::graph::ViewportRect::from_size(context.resolution),
-        );
-        // Add the default rendergraph
-        context.base_rendergraph.add_to_graph(
-            &mut graph,
-            rend3_routine::base::BaseRenderGraphInputs {
-                eval_output: &eval_output,
-                routines: rend3_routine::base::BaseRenderGraphRoutines {
-                    pbr: &pbr_routine,
-                    skybox: Some(&skybox_routine),
-                    tonemapping: &tonemapping_routine,
+        //  Day and night. Move the sun, and change the sky, for this frame.
+        let sky = self
+            .day_cycle
+            .map(|cycle| cycle.sky((now - self.timestamp_start).as_secs_f32()));
+        if let Some(sky) = sky {
+            self.update_sky(context.renderer, &mut skybox_routine, sky);
+        }
+        self.swap_skybox(context.renderer, &mut skybox_routine, now);
+
+        //  Each view gets its own camera, evaluation, and render graph, since
+        //  Rend3 takes up a camera only when evaluating. See views.rs.
+        let main = Pose {
+            location: self.camera_location,
+            yaw: self.camera_yaw,
+            pitch: self.camera_pitch,
+        };
+        let secs = (now - self.timestamp_start).as_secs_f32();
+        let extent = self.city_extent();
+        let mut passes: Vec<_> = self
+            .split
+            .viewports(context.resolution)
+            .into_iter()
+            .enumerate()
+            .map(|(n, rect)| {
+                let pose = views::view_pose(n, main, secs, extent);
+                (views::view_name(n), rect, pose, self.camera_settings)
+            })
+            .collect();
+        //  The security camera monitor is one more pass, last, so it is drawn on top.
+        if self.monitor {
+            let settings = CameraSettings {
+                projection: Projection::Perspective,
+                vfov: views::MONITOR_FOV,
+                ..self.camera_settings
+            };
+            let rect = views::monitor_viewport(context.resolution);
+            passes.push(("monitor", rect, views::monitor_pose(secs, extent), settings));
+        }
+        let split = passes.len() > 1;
+        let mut frame_scopes = Vec::new(); // GPU timing for all views
+        for (name, (offset, size), pose, settings) in passes {
+            let view_start = Instant::now();
+            let aspect = size.x as f32 / size.y.max(1) as f32;
+            context.renderer.set_camera_data(Camera {
+                projection: settings.camera_projection(extent, aspect),
+                view: pose.view_matrix(),
+            });
+            //  Swap the instruction buffers. This begins a new frame.
+            context.renderer.swap_instruction_buffers();
+
+            // Ready up the renderer
+            // Ready up the routines
+            let mut eval_output = context.renderer.evaluate_instructions();
+            skybox_routine.evaluate(context.renderer);
+
+            // Build a rendergraph
+            //  The base graph's targets are at the render scale. Tonemapping
+            //  scales them to fit the view's rectangle of the surface.
+            let mut graph = rend3::graph::RenderGraph::new();
+            let frame_handle = graph.add_imported_render_target(
+                context.surface_texture,
+                0..1,
+                0..1,
+                rend3::graph::ViewportRect::new(offset, size),
+            );
+            // Add the default rendergraph
+            context.base_rendergraph.add_to_graph(
+                &mut graph,
+                rend3_routine::base::BaseRenderGraphInputs {
+                    eval_output: &eval_output,
+                    routines: rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: Some(&skybox_routine),
+                        tonemapping: &tonemapping_routine,
+                    },
+                    target: rend3_routine::base::OutputRenderTarget {
+                        handle: frame_handle,
+                        resolution: self.render_scale.internal(size),
+                        samples: self.samples,
+                    },
                 },
-                target: rend3_routine::base::OutputRenderTarget {
-                    handle: frame_handle,
-                    resolution: context.resolution,
-                    samples: self.samples,
+                rend3_routine::base::BaseRenderGraphSettings {
+                    ambient_color: Vec3::splat(
+                        sky.map_or(self.ambient_light_level, |sky| sky.ambient),
+                    )
+                    .extend(1.0),
+                    clear_color: sky
+                        .map_or(glam::Vec4::new(0.0, 0.0, 0.0, 1.0), |sky| sky.clear_color),
                 },
-            },
-            rend3_routine::base::BaseRenderGraphSettings {
-                ambient_color: Vec3::splat(self.ambient_light_level).extend(1.0),
-                clear_color: glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
-            },
-        );
+            );
 
-        // Dispatch a render using the built up rendergraph!
-        self.previous_profiling_stats = graph.execute(context.renderer, &mut eval_output);
+            // Dispatch a render using the built up rendergraph!
+            let scopes = graph
+                .execute(context.renderer, &mut eval_output)
+                .unwrap_or_default();
+            if split {
+                self.view_stats.record_cpu(name, view_start.elapsed());
+                frame_scopes.extend(ViewTimingStats::gpu_scope(name, scopes));
+            } else {
+                frame_scopes = scopes;
+            }
+        }
+        //  All views' GPU timing, as one frame.
+        if !frame_scopes.is_empty() {
+            self.gpu_stats.accumulate(&frame_scopes);
+            self.previous_profiling_stats = Some(frame_scopes);
+        }
 
         // mark the end of the frame for tracy/other profilers
         profiling::finish_frame!();
@@ -675,9 +997,7 @@ impl rend3_framework::App for SceneViewer {
             Event::LoopExiting {
                 ..
             } => {
-                println!("Starting shutdown.");
-                self.city_builder.stop(); // shut down other threads
-                println!("Exiting.");
+                self.shutdown();
                 ////control_flow(winit::event_loop::ControlFlow::Exit);
                 ////std::process::exit(0); // Is there no better way to exit than this? 
             }
@@ -687,6 +1007,101 @@ impl rend3_framework::App for SceneViewer {
 }
 
 impl SceneViewer {
+    /// Stop other threads and save results.
+    fn shutdown(&mut self) {
+        println!("Starting shutdown.");
+        self.city_builder.stop(); // shut down other threads
+        if let Some((ref filename, ref results)) = self.results {
+            match results.save(filename) {
+                Ok(()) => println!("Saved {} frame times to {}", results.frames.len(), filename),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        println!("Exiting.");
+    }
+
+    /// Width of the city, for the top-down view. Around the camera, if streaming.
+    fn city_extent(&self) -> f32 {
+        let params = &self.city_builder.params;
+        match params.stream_radius {
+            Some(radius) => radius * 2.0,
+            None => params.bldg_rows as f32 * params.cell_spacing(),
+        }
+    }
+
+    /// Move camera along the fixed flythrough path.
+    //  Circles the city, looking at the center, so every run sees the same thing.
+    fn fly_camera(&mut self, now: Instant) {
+        const FLYTHROUGH_PERIOD: f32 = 60.0; // secs per orbit
+        const FLYTHROUGH_HEIGHT: f32 = 40.0; // meters above ground
+        let params = &self.city_builder.params;
+        let radius = params.bldg_rows as f32 * params.cell_spacing() * 0.6;
+        let angle = std::f32::consts::TAU * (now - self.timestamp_start).as_secs_f32()
+            / FLYTHROUGH_PERIOD;
+        self.camera_location =
+            Vec3A::new(radius * angle.cos(), FLYTHROUGH_HEIGHT, radius * angle.sin());
+        //  Point camera at center of city.
+        //  Forward is (-cos(pitch) sin(yaw), sin(pitch), -cos(pitch) cos(yaw)).
+        let dir = (-self.camera_location).normalize();
+        self.camera_pitch = dir.y.asin();
+        self.camera_yaw = (-dir.x).atan2(-dir.z);
+    }
+
+    /// Move the sun and change the sky, for the day cycle.
+    //  The skybox is only switched when it appears or disappears.
+    fn update_sky(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        skybox_routine: &mut SkyboxRoutine,
+        sky: Sky,
+    ) {
+        if let Some(light) = &self.directional_light {
+            let change = DirectionalLightChange {
+                color: Some(sky.color),
+                intensity: Some(sky.intensity),
+                direction: Some(sky.direction),
+                distance: None,
+                resolution: None,
+            };
+            stats::timed(Op::UpdateLight, || {
+                renderer.update_directional_light(light, change)
+            });
+        }
+        if sky.show_skybox != self.skybox_shown {
+            let background = if sky.show_skybox {
+                self.skybox.clone()
+            } else {
+                None
+            };
+            skybox_routine.set_background_texture(background);
+            self.skybox_shown = sky.show_skybox;
+        }
+    }
+
+    /// Upload the skybox again, as a new cube texture, if it is time.
+    //  The new one replaces the old one, which is then dropped.
+    fn swap_skybox(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        skybox_routine: &mut SkyboxRoutine,
+        now: Instant,
+    ) {
+        let (interval, last) = match self.skybox_swap {
+            Some(swap) => swap,
+            None => return,
+        };
+        if now < last + interval {
+            return;
+        }
+        self.skybox_swap = Some((interval, now));
+        if let Some(image) = &self.skybox_image {
+            self.skybox = Some(image.upload(renderer));
+            if self.skybox_shown {
+                skybox_routine.set_background_texture(self.skybox.clone());
+            }
+        }
+    }
+
     /// Handle movement from key presses.
     /// Follows how SceneViewer example does it.
     fn handle_button(&mut self, context: &rend3_framework::RedrawContext<'_, ()>, delta_time: Duration) {              
@@ -729,19 +1144,16 @@ impl SceneViewer {
         if button_pressed(&self.scancode_status, KeyCode::Escape) {
             self.grabber.as_mut().unwrap().request_ungrab(context.window.as_ref().unwrap());
         }
-        if button_pressed(&self.scancode_status, KeyCode::KeyP) {
-            // write out gpu side performance info into a trace readable by chrome://tracing
-            if let Some(ref stats) = self.previous_profiling_stats {
-                println!("Outputing gpu timing chrome trace to profile.json");
-                wgpu_profiler::chrometrace::write_chrometrace(
-                    Path::new("profile.json"),
-                    stats,
-                )
-                .unwrap();
+        let trace_key_down = button_pressed(&self.scancode_status, KeyCode::KeyP);
+        if trace_key_down && !self.gpu_trace_key_down {
+            // capture gpu side performance info over the next frames, into a trace readable by chrome://tracing
+            if self.previous_profiling_stats.is_some() {
+                self.gpu_stats.start_trace();
             } else {
                 println!("No gpu timing trace available, either timestamp queries are unsupported or not enough frames have elapsed yet!");
             }
         }
+        self.gpu_trace_key_down = trace_key_down;
     }
 }
 
@@ -760,9 +1172,11 @@ pub fn viewer() {
 
     let app = SceneViewer::new();
 
-    let mut builder = WindowBuilder::new()
-        .with_title("render-bench")
-        .with_maximized(true);
+    let mut builder = WindowBuilder::new().with_title("render-bench");
+    builder = match app.resolution {
+        Some(size) => builder.with_inner_size(winit::dpi::PhysicalSize::new(size.x, size.y)),
+        None => builder.with_maximized(true),
+    };
     if app.fullscreen {
         builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
     }
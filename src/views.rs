@@ -0,0 +1,168 @@
+//  views.rs -- split-screen views.
+//
+//  Part of render-bench.
+//
+//  The window can be split into two or four views of the same city, each
+//  with its own camera, like picture-in-picture views and mirrors. The
+//  first view is the main camera, which walks or flies as usual. The others
+//  follow from it: a rear view mirror, a camera circling the city, and one
+//  looking down from above the main camera.
+//
+//  Rend3 at this version has one camera, set with set_camera_data and taken
+//  up only at the next swap_instruction_buffers and evaluate_instructions.
+//  The base render graph has no camera input of its own; its culling and
+//  uniforms use that one camera when the graph executes. So views with
+//  different cameras cannot share one evaluation or one graph. Each view is
+//  evaluated and rendered in turn, into its own rectangle of the same
+//  surface texture. Scene changes from the builder threads go in with
+//  whichever view's evaluation comes after them, so the extra evaluations
+//  are not just the camera. Each view's CPU and GPU time is reported.
+//
+//  The security camera monitor is one more pass of the same kind, from a
+//  camera on a pole at the corner of the city, panning back and forth, drawn
+//  last, in a corner of the window. Its CPU and GPU time is reported as the
+//  "monitor" view: the cost of a second full scene pass every frame.
+//
+//  The monitor is not rendered into a texture used on building faces. A
+//  PbrMaterial samples Texture2DHandles, which the Renderer makes from pixel
+//  data, add_texture_2d, or from another handle, add_texture_2d_from_texture.
+//  Drawing into one would take the wgpu texture behind a handle, from the
+//  renderer's texture storage, imported into the graph as a render target or
+//  copied into from a graph node. That is outside the public calls the rest
+//  of this benchmark uses, and whether this Rend3 version allows it has not
+//  been established, so render to texture is left out.
+//
+use core::f32::consts::{FRAC_PI_3, PI, TAU};
+use glam::{Mat4, UVec2, Vec3A};
+
+/// Circling camera, seconds per orbit.
+const ORBIT_PERIOD: f32 = 90.0;
+/// Circling camera, meters above ground.
+const ORBIT_HEIGHT: f32 = 60.0;
+/// Overhead camera, meters above the main camera.
+const OVERHEAD_HEIGHT: f32 = 80.0;
+/// Security camera field of view, degrees. Wide.
+pub const MONITOR_FOV: f32 = 75.0;
+/// Security camera, meters above ground.
+const MONITOR_HEIGHT: f32 = 25.0;
+/// Security camera, seconds per pan back and forth.
+const MONITOR_PAN_PERIOD: f32 = 20.0;
+/// Security camera, radians panned either side of the city center.
+const MONITOR_PAN: f32 = 0.5;
+/// Monitor size, as a fraction of the window's width and height.
+const MONITOR_FRACTION: u32 = 4;
+
+/// How the window is split.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Split {
+    One,  // the whole window
+    Two,  // left and right halves
+    Four, // quarters
+}
+
+impl Split {
+    /// Parse from command line. Number of views.
+    pub fn parse(s: &str) -> Result<Split, &'static str> {
+        match s {
+            "1" => Ok(Split::One),
+            "2" => Ok(Split::Two),
+            "4" => Ok(Split::Four),
+            _ => Err("Views must be 1, 2, or 4"),
+        }
+    }
+
+    /// Rectangle of each view, as offset and size, in view order.
+    pub fn viewports(&self, resolution: UVec2) -> Vec<(UVec2, UVec2)> {
+        let half = UVec2::new(resolution.x / 2, resolution.y / 2).max(UVec2::ONE);
+        match self {
+            Split::One => vec![(UVec2::ZERO, resolution)],
+            Split::Two => vec![
+                (UVec2::ZERO, UVec2::new(half.x, resolution.y)),
+                (UVec2::new(half.x, 0), UVec2::new(half.x, resolution.y)),
+            ],
+            Split::Four => vec![
+                (UVec2::ZERO, half),
+                (UVec2::new(half.x, 0), half),
+                (UVec2::new(0, half.y), half),
+                (half, half),
+            ],
+        }
+    }
+}
+
+/// Where a camera is and which way it looks.
+#[derive(Debug, Copy, Clone)]
+pub struct Pose {
+    pub location: Vec3A, // camera position
+    pub yaw: f32,        // heading, radians
+    pub pitch: f32,      // up positive, radians
+}
+
+impl Pose {
+    /// View matrix for Rend3.
+    pub fn view_matrix(&self) -> Mat4 {
+        let view = Mat4::from_euler(glam::EulerRot::XYZ, -self.pitch, -self.yaw, 0.0);
+        view * Mat4::from_translation((-self.location).into())
+    }
+}
+
+/// Name of view n, for reports.
+pub fn view_name(n: usize) -> &'static str {
+    match n {
+        0 => "main",
+        1 => "rear",
+        2 => "orbit",
+        _ => "overhead",
+    }
+}
+
+/// Pose of view n, given the main camera's pose, seconds since startup, and the width of the city.
+pub fn view_pose(n: usize, main: Pose, secs: f32, extent: f32) -> Pose {
+    match n {
+        0 => main,
+        //  Rear view mirror. Same place, turned around.
+        1 => Pose {
+            yaw: (main.yaw + PI).rem_euclid(TAU),
+            ..main
+        },
+        //  Circling the city, looking at the center.
+        2 => {
+            let angle = TAU * secs / ORBIT_PERIOD + PI; // other side from the flythrough
+            let radius = extent * 0.6;
+            let location = Vec3A::new(radius * angle.cos(), ORBIT_HEIGHT, radius * angle.sin());
+            let dir = (-location).normalize();
+            Pose {
+                location,
+                yaw: (-dir.x).atan2(-dir.z),
+                pitch: dir.y.asin(),
+            }
+        }
+        //  Above the main camera, looking steeply down the same way.
+        _ => Pose {
+            location: main.location + Vec3A::new(0.0, OVERHEAD_HEIGHT, 0.0),
+            yaw: main.yaw,
+            pitch: -FRAC_PI_3,
+        },
+    }
+}
+
+/// Rectangle of the security camera monitor, as offset and size. Lower right corner of the window.
+pub fn monitor_viewport(resolution: UVec2) -> (UVec2, UVec2) {
+    let size = (resolution / MONITOR_FRACTION).max(UVec2::ONE);
+    let margin = size / 8;
+    (resolution - size - margin, size)
+}
+
+/// Pose of the security camera, seconds since startup, given the width of the city.
+//  At one corner of the city, panning back and forth across it.
+pub fn monitor_pose(secs: f32, extent: f32) -> Pose {
+    let corner = extent * 0.5;
+    let location = Vec3A::new(corner, MONITOR_HEIGHT, corner);
+    let dir = (-location).normalize();
+    let pan = MONITOR_PAN * (TAU * secs / MONITOR_PAN_PERIOD).sin();
+    Pose {
+        location,
+        yaw: (-dir.x).atan2(-dir.z) + pan,
+        pitch: dir.y.asin(),
+    }
+}